@@ -0,0 +1,619 @@
+// Assembler for the Octo syntax written by the disassembler.
+//
+// Supported: instructions, `: label`, `:const NAME value`, `:call target`, bare label names as
+// calls, numbers as data bytes, `:byte value` and `:include "file"`. Comments start with `#`.
+// Labels and constants may be used as operands before they are defined, but the value of a
+// `:const` and a constant written on its own as a data byte must already be known.
+
+use std::collections::HashMap;
+use std::error;
+use std::fmt;
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use instruction::Instruction;
+
+#[cfg(test)]
+mod tests;
+
+// where ROMs are loaded
+const ORIGIN: u32 = 0x200;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AsmError {
+    pub file: String,
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for AsmError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.file, self.line, self.message)
+    }
+}
+
+impl error::Error for AsmError {
+    fn description(&self) -> &str {
+        &self.message
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Token {
+    text: String,
+    file: usize,
+    line: usize,
+}
+
+// A number, or the name of a label or constant resolved once the whole program is read
+#[derive(Debug, Clone)]
+enum Operand {
+    Number(u32),
+    Name(String),
+}
+
+enum Item {
+    Bytes(Vec<u8>),
+    // bytes that depend on an operand, built once every name is known
+    Deferred {
+        operand: Operand,
+        token: Token,
+        build: Box<Fn(u32) -> Result<Vec<u8>, String>>,
+    },
+}
+
+struct Assembler {
+    files: Vec<String>,
+    tokens: Vec<Token>,
+    pos: usize,
+    items: Vec<Item>,
+    address: u32,
+    labels: HashMap<String, u32>,
+    constants: HashMap<String, u32>,
+    // files being included, innermost last, to catch a file that includes itself
+    including: Vec<PathBuf>,
+}
+
+/// Assembles source text. Includes are resolved relative to the current directory.
+pub fn assemble(source: &str) -> Result<Vec<u8>, AsmError> {
+    let mut asm = Assembler::new();
+    try!(asm.tokenize(source, "<source>", Path::new(".")));
+    asm.run()
+}
+
+/// Assembles a source file. Includes are resolved relative to the including file.
+pub fn assemble_file<P: AsRef<Path>>(path: P) -> Result<Vec<u8>, AsmError> {
+    let mut asm = Assembler::new();
+    try!(asm.include(path.as_ref(), None));
+    asm.run()
+}
+
+fn read_file(path: &Path) -> Result<String, String> {
+    let mut source = String::new();
+    try!(File::open(path)
+             .and_then(|mut f| f.read_to_string(&mut source))
+             .map_err(|e| format!("could not read {}: {}", path.display(), e)));
+    Ok(source)
+}
+
+fn parse_number(text: &str) -> Option<u32> {
+    let (negative, text) = if text.starts_with('-') {
+        (true, &text[1..])
+    } else {
+        (false, text)
+    };
+    let value = if text.starts_with("0x") || text.starts_with("0X") {
+        u32::from_str_radix(&text[2..], 16).ok()
+    } else if text.starts_with("0b") || text.starts_with("0B") {
+        u32::from_str_radix(&text[2..], 2).ok()
+    } else {
+        text.parse().ok()
+    };
+    // negative numbers wrap, so `v0 += -1` is `v0 += 0xFF`
+    value.map(|v| if negative {
+        (v as i64).wrapping_neg() as u32
+    } else {
+        v
+    })
+}
+
+fn parse_register(text: &str) -> Option<u8> {
+    let lower = text.to_lowercase();
+    if lower.len() == 2 && lower.starts_with('v') {
+        u8::from_str_radix(&lower[1..], 16).ok()
+    } else {
+        None
+    }
+}
+
+fn fits(value: u32, bits: u32, what: &str) -> Result<u32, String> {
+    // allow negative values that wrapped, as long as they fit once truncated
+    let mask = (1 << bits) - 1;
+    let signed = value as i32;
+    if value <= mask || (signed < 0 && signed >= -(1 << bits)) {
+        Ok(value & mask)
+    } else {
+        Err(format!("{} 0x{:X} does not fit in {} bits", what, value, bits))
+    }
+}
+
+fn word(instruction: Instruction) -> Vec<u8> {
+    let op = instruction.encode();
+    vec![(op >> 8) as u8, op as u8]
+}
+
+impl Assembler {
+    fn new() -> Assembler {
+        Assembler {
+            files: Vec::new(),
+            tokens: Vec::new(),
+            pos: 0,
+            items: Vec::new(),
+            address: ORIGIN,
+            labels: HashMap::new(),
+            constants: HashMap::new(),
+            including: Vec::new(),
+        }
+    }
+
+    fn include(&mut self, path: &Path, from: Option<&Token>) -> Result<(), AsmError> {
+        let canonical = path.canonicalize().unwrap_or(path.to_path_buf());
+        let source = if self.including.contains(&canonical) {
+            Err(format!("{} includes itself", path.display()))
+        } else {
+            read_file(path)
+        };
+        let source = match source {
+            Ok(source) => source,
+            Err(message) => {
+                return Err(match from {
+                    Some(token) => self.error(token, message),
+                    None => {
+                        AsmError {
+                            file: path.display().to_string(),
+                            line: 0,
+                            message: message,
+                        }
+                    }
+                })
+            }
+        };
+        let base = path.parent().map(|p| p.to_path_buf()).unwrap_or(PathBuf::from("."));
+        self.including.push(canonical);
+        let result = self.tokenize(&source, &path.display().to_string(), &base);
+        self.including.pop();
+        result
+    }
+
+    // Splits source into tokens, expanding includes in place
+    fn tokenize(&mut self, source: &str, name: &str, base: &Path) -> Result<(), AsmError> {
+        let file = self.files.len();
+        self.files.push(String::from(name));
+        for (n, line) in source.lines().enumerate() {
+            let line = match line.find('#') {
+                Some(i) => &line[..i],
+                None => line,
+            };
+            let mut words = line.split_whitespace().peekable();
+            while let Some(word) = words.next() {
+                let token = Token {
+                    text: String::from(word),
+                    file: file,
+                    line: n + 1,
+                };
+                if word == ":include" {
+                    // the path is quoted and may contain spaces
+                    let mut path = String::new();
+                    for part in words.by_ref() {
+                        if !path.is_empty() {
+                            path.push(' ');
+                        }
+                        path.push_str(part);
+                        if path.len() > 1 && path.ends_with('"') {
+                            break;
+                        }
+                    }
+                    if !(path.len() > 1 && path.starts_with('"') && path.ends_with('"')) {
+                        let message = String::from(":include needs a quoted path");
+                        return Err(self.error(&token, message));
+                    }
+                    let path = base.join(&path[1..path.len() - 1]);
+                    try!(self.include(&path, Some(&token)));
+                } else {
+                    self.tokens.push(token);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn error(&self, token: &Token, message: String) -> AsmError {
+        AsmError {
+            file: self.files[token.file].clone(),
+            line: token.line,
+            message: message,
+        }
+    }
+
+    fn run(mut self) -> Result<Vec<u8>, AsmError> {
+        while self.pos < self.tokens.len() {
+            try!(self.statement());
+        }
+
+        let mut rom = Vec::new();
+        for item in &self.items {
+            match *item {
+                Item::Bytes(ref bytes) => rom.extend_from_slice(bytes),
+                Item::Deferred { ref operand, ref token, ref build } => {
+                    let value = try!(self.resolve(operand, token));
+                    let bytes = try!(build(value).map_err(|m| self.error(token, m)));
+                    rom.extend_from_slice(&bytes);
+                }
+            }
+        }
+        Ok(rom)
+    }
+
+    fn resolve(&self, operand: &Operand, token: &Token) -> Result<u32, AsmError> {
+        match *operand {
+            Operand::Number(n) => Ok(n),
+            Operand::Name(ref name) => {
+                match self.labels.get(name).or(self.constants.get(name)) {
+                    Some(&v) => Ok(v),
+                    None => Err(self.error(token, format!("undefined name {}", name))),
+                }
+            }
+        }
+    }
+
+    fn next(&mut self) -> Result<Token, AsmError> {
+        match self.tokens.get(self.pos) {
+            Some(token) => {
+                self.pos += 1;
+                Ok(token.clone())
+            }
+            None => {
+                let last = self.tokens.last().unwrap().clone();
+                Err(self.error(&last, String::from("unexpected end of input")))
+            }
+        }
+    }
+
+    fn peek(&self) -> Option<&str> {
+        self.tokens.get(self.pos).map(|t| &*t.text)
+    }
+
+    fn expect(&mut self, text: &str) -> Result<(), AsmError> {
+        let token = try!(self.next());
+        if token.text == text {
+            Ok(())
+        } else {
+            Err(self.error(&token, format!("expected {}, found {}", text, token.text)))
+        }
+    }
+
+    fn register(&mut self) -> Result<u8, AsmError> {
+        let token = try!(self.next());
+        parse_register(&token.text)
+            .ok_or_else(|| self.error(&token, format!("expected a register, found {}", token.text)))
+    }
+
+    fn operand(&mut self) -> Result<(Operand, Token), AsmError> {
+        let token = try!(self.next());
+        let operand = match parse_number(&token.text) {
+            Some(n) => Operand::Number(n),
+            None if parse_register(&token.text).is_none() && !token.text.starts_with(':') => {
+                Operand::Name(token.text.clone())
+            }
+            None => {
+                return Err(self.error(&token,
+                                      format!("expected a number or name, found {}", token.text)))
+            }
+        };
+        Ok((operand, token))
+    }
+
+    fn emit(&mut self, bytes: Vec<u8>) {
+        self.address += bytes.len() as u32;
+        self.items.push(Item::Bytes(bytes));
+    }
+
+    fn emit_instruction(&mut self, instruction: Instruction) {
+        self.emit(word(instruction));
+    }
+
+    fn defer(&mut self,
+             operand: Operand,
+             token: Token,
+             len: u32,
+             build: Box<Fn(u32) -> Result<Vec<u8>, String>>) {
+        self.address += len;
+        self.items.push(Item::Deferred {
+            operand: operand,
+            token: token,
+            build: build,
+        });
+    }
+
+    // Emits an instruction taking a 12 bit address
+    fn address_instruction(&mut self, make: fn(u16) -> Instruction) -> Result<(), AsmError> {
+        let (operand, token) = try!(self.operand());
+        self.defer(operand,
+                   token,
+                   2,
+                   Box::new(move |v| fits(v, 12, "address").map(|v| word(make(v as u16)))));
+        Ok(())
+    }
+
+    // Emits an instruction taking a register and an 8 bit immediate
+    fn immediate_instruction(&mut self,
+                             x: u8,
+                             make: fn(u8, u8) -> Instruction)
+                             -> Result<(), AsmError> {
+        let (operand, token) = try!(self.operand());
+        self.defer(operand,
+                   token,
+                   2,
+                   Box::new(move |v| fits(v, 8, "value").map(|v| word(make(x, v as u8)))));
+        Ok(())
+    }
+
+    // Emits an instruction taking a 4 bit immediate, such as a sprite height
+    fn nibble_instruction(&mut self, make: Box<Fn(u8) -> Instruction>) -> Result<(), AsmError> {
+        let (operand, token) = try!(self.operand());
+        self.defer(operand,
+                   token,
+                   2,
+                   Box::new(move |v| fits(v, 4, "value").map(|v| word(make(v as u8)))));
+        Ok(())
+    }
+
+    fn statement(&mut self) -> Result<(), AsmError> {
+        use instruction::Instruction::*;
+
+        let token = try!(self.next());
+        match &*token.text {
+            ":" => {
+                let name = try!(self.next());
+                if self.labels.contains_key(&name.text) {
+                    let message = format!("label {} is already defined", name.text);
+                    return Err(self.error(&name, message));
+                }
+                self.labels.insert(name.text, self.address);
+            }
+            ":const" => {
+                let name = try!(self.next());
+                let (operand, token) = try!(self.operand());
+                let value = try!(self.resolve(&operand, &token));
+                self.constants.insert(name.text, value);
+            }
+            ":call" => try!(self.address_instruction(Call)),
+            ":byte" => {
+                let (operand, token) = try!(self.operand());
+                self.defer(operand,
+                           token,
+                           1,
+                           Box::new(|v| fits(v, 8, "byte").map(|v| vec![v as u8])));
+            }
+            "clear" => self.emit_instruction(Clear),
+            "return" => self.emit_instruction(Return),
+            "exit" => self.emit_instruction(Exit),
+            "lores" => self.emit_instruction(Lores),
+            "hires" => self.emit_instruction(Hires),
+            "scroll-left" => self.emit_instruction(ScrollLeft),
+            "scroll-right" => self.emit_instruction(ScrollRight),
+            "scroll-down" => try!(self.nibble_instruction(Box::new(ScrollDown))),
+            "scroll-up" => try!(self.nibble_instruction(Box::new(ScrollUp))),
+            "plane" => try!(self.nibble_instruction(Box::new(Plane))),
+            "audio" => self.emit_instruction(Audio),
+            "jump" => try!(self.address_instruction(Jump)),
+            "jump0" => try!(self.address_instruction(JumpOffset)),
+            "sprite" => {
+                let x = try!(self.register());
+                let y = try!(self.register());
+                try!(self.nibble_instruction(Box::new(move |n| Draw(x, y, n))));
+            }
+            "bcd" => {
+                let x = try!(self.register());
+                self.emit_instruction(Bcd(x));
+            }
+            "save" | "load" => {
+                let x = try!(self.register());
+                let save = token.text == "save";
+                if self.peek() == Some("-") {
+                    try!(self.next());
+                    let y = try!(self.register());
+                    self.emit_instruction(if save {
+                        SaveRange(x, y)
+                    } else {
+                        LoadRange(x, y)
+                    });
+                } else {
+                    self.emit_instruction(if save {
+                        Save(x)
+                    } else {
+                        Load(x)
+                    });
+                }
+            }
+            "saveflags" => {
+                let x = try!(self.register());
+                self.emit_instruction(SaveFlags(x));
+            }
+            "loadflags" => {
+                let x = try!(self.register());
+                self.emit_instruction(LoadFlags(x));
+            }
+            "delay" | "buzzer" | "pitch" => {
+                try!(self.expect(":="));
+                let x = try!(self.register());
+                self.emit_instruction(match &*token.text {
+                    "delay" => SetDelay(x),
+                    "buzzer" => SetSound(x),
+                    _ => Pitch(x),
+                });
+            }
+            "i" | "I" => try!(self.index_statement()),
+            "if" => try!(self.if_statement()),
+            text => {
+                if let Some(x) = parse_register(text) {
+                    try!(self.register_statement(x));
+                } else if let Some(n) = parse_number(text) {
+                    let byte = try!(fits(n, 8, "byte").map_err(|m| self.error(&token, m)));
+                    self.emit(vec![byte as u8]);
+                } else if text.starts_with(':') {
+                    return Err(self.error(&token, format!("unknown directive {}", text)));
+                } else if self.constants.contains_key(text) {
+                    let value = self.constants[text];
+                    let byte = try!(fits(value, 8, "byte").map_err(|m| self.error(&token, m)));
+                    self.emit(vec![byte as u8]);
+                } else {
+                    // anything else names a subroutine to call
+                    let operand = Operand::Name(String::from(text));
+                    self.defer(operand,
+                               token.clone(),
+                               2,
+                               Box::new(|v| fits(v, 12, "address").map(|v| word(Call(v as u16)))));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn index_statement(&mut self) -> Result<(), AsmError> {
+        use instruction::Instruction::*;
+
+        let op = try!(self.next());
+        match &*op.text {
+            ":=" => {
+                match self.peek() {
+                    Some("hex") | Some("bighex") => {
+                        let big = try!(self.next()).text == "bighex";
+                        let x = try!(self.register());
+                        self.emit_instruction(if big {
+                            BigFont(x)
+                        } else {
+                            Font(x)
+                        });
+                    }
+                    Some("long") => {
+                        try!(self.next());
+                        let (operand, token) = try!(self.operand());
+                        self.defer(operand,
+                                   token,
+                                   4,
+                                   Box::new(|v| {
+                                       fits(v, 16, "address").map(|v| {
+                                           vec![0xF0, 0x00, (v >> 8) as u8, v as u8]
+                                       })
+                                   }));
+                    }
+                    _ => try!(self.address_instruction(LoadI)),
+                }
+            }
+            "+=" => {
+                let x = try!(self.register());
+                self.emit_instruction(AddI(x));
+            }
+            _ => return Err(self.error(&op, format!("unknown operator i {}", op.text))),
+        }
+        Ok(())
+    }
+
+    fn register_statement(&mut self, x: u8) -> Result<(), AsmError> {
+        use instruction::Instruction::*;
+
+        let op = try!(self.next());
+        let register_op: fn(u8, u8) -> Instruction = match &*op.text {
+            ":=" => Move,
+            "|=" => Or,
+            "&=" => And,
+            "^=" => Xor,
+            "+=" => Add,
+            "-=" => Sub,
+            ">>=" => ShiftRight,
+            "=-" => SubReverse,
+            "<<=" => ShiftLeft,
+            _ => return Err(self.error(&op, format!("unknown operator {}", op.text))),
+        };
+
+        if let Some(y) = self.peek().and_then(parse_register) {
+            try!(self.next());
+            self.emit_instruction(register_op(x, y));
+            return Ok(());
+        }
+        match &*op.text {
+            ":=" => {
+                match self.peek() {
+                    Some("random") => {
+                        try!(self.next());
+                        try!(self.immediate_instruction(x, Random));
+                    }
+                    Some("delay") => {
+                        try!(self.next());
+                        self.emit_instruction(GetDelay(x));
+                    }
+                    Some("key") => {
+                        try!(self.next());
+                        self.emit_instruction(WaitKey(x));
+                    }
+                    _ => try!(self.immediate_instruction(x, LoadImm)),
+                }
+            }
+            "+=" => try!(self.immediate_instruction(x, AddImm)),
+            "-=" => {
+                // there is no subtract immediate, add the negated value instead
+                let (operand, token) = try!(self.operand());
+                self.defer(operand,
+                           token,
+                           2,
+                           Box::new(move |v| {
+                               fits(v, 8, "value")
+                                   .map(|v| word(AddImm(x, (v as u8).wrapping_neg())))
+                           }));
+            }
+            _ => {
+                return Err(self.error(&op, format!("{} needs a register operand", op.text)));
+            }
+        }
+        Ok(())
+    }
+
+    fn if_statement(&mut self) -> Result<(), AsmError> {
+        use instruction::Instruction::*;
+
+        let x = try!(self.register());
+        let op = try!(self.next());
+        // each condition compiles to the skip that jumps over the next line when it is false
+        match &*op.text {
+            "key" | "-key" => {
+                self.emit_instruction(if op.text == "key" {
+                    SkipNotKey(x)
+                } else {
+                    SkipKey(x)
+                });
+            }
+            "==" | "!=" => {
+                let equal = op.text == "==";
+                if let Some(y) = self.peek().and_then(parse_register) {
+                    try!(self.next());
+                    self.emit_instruction(if equal {
+                        SkipNeReg(x, y)
+                    } else {
+                        SkipEqReg(x, y)
+                    });
+                } else {
+                    try!(self.immediate_instruction(x,
+                                                    if equal {
+                                                        SkipNeImm
+                                                    } else {
+                                                        SkipEqImm
+                                                    }));
+                }
+            }
+            _ => return Err(self.error(&op, format!("unknown condition {}", op.text))),
+        }
+        self.expect("then")
+    }
+}
@@ -0,0 +1,120 @@
+use super::*;
+use std::env;
+use std::fs::{self, File};
+use std::io::Write;
+
+use chip8::Chip8;
+use disasm::disassemble;
+
+#[test]
+fn assemble_instructions() {
+    let rom = assemble("clear\n\
+                        v0 := 0x03 # load three\n\
+                        i := hex v0\n\
+                        sprite v1 v2 5\n\
+                        if v0 != v1 then v3 += -1\n\
+                        v4 -= 2\n\
+                        save v0 - v3\n\
+                        i := long 0x1234\n\
+                        exit")
+        .unwrap();
+    assert_eq!(rom,
+               vec![0x00, 0xE0, 0x60, 0x03, 0xF0, 0x29, 0xD1, 0x25, 0x50, 0x10, 0x73, 0xFF,
+                    0x74, 0xFE, 0x50, 0x32, 0xF0, 0x00, 0x12, 0x34, 0x00, 0xFD]);
+}
+
+#[test]
+fn assemble_labels_and_constants() {
+    let rom = assemble(":const SPEED 4\n\
+                        : main\n\
+                        \tv0 := SPEED\n\
+                        \tdraw\n\
+                        \tjump main\n\
+                        : draw\n\
+                        \ti := sprite\n\
+                        \t:call done\n\
+                        : done\n\
+                        \treturn\n\
+                        : sprite\n\
+                        \t0xFF 0b10000001 SPEED")
+        .unwrap();
+    assert_eq!(rom,
+               vec![0x60, 0x04, 0x22, 0x06, 0x12, 0x00, 0xA2, 0x0C, 0x22, 0x0A, 0x00, 0xEE,
+                    0xFF, 0x81, 0x04]);
+}
+
+#[test]
+fn assemble_errors() {
+    let err = assemble("clear\n\tjump nowhere").unwrap_err();
+    assert_eq!(err.line, 2);
+    assert_eq!(err.message, "undefined name nowhere");
+
+    let err = assemble("v0 := 0x100").unwrap_err();
+    assert_eq!(err.message, "value 0x100 does not fit in 8 bits");
+
+    let err = assemble("sprite v0 i 1").unwrap_err();
+    assert_eq!(err.message, "expected a register, found i");
+}
+
+#[test]
+fn assemble_include() {
+    let dir = env::temp_dir().join("chip8-asm-include");
+    fs::create_dir_all(&dir).unwrap();
+    File::create(dir.join("font.8o")).unwrap().write_all(b": glyph\n0x0F 0xF0\n").unwrap();
+    File::create(dir.join("main.8o"))
+        .unwrap()
+        .write_all(b"i := glyph\n:include \"font.8o\"\n")
+        .unwrap();
+    assert_eq!(assemble_file(dir.join("main.8o")).unwrap(),
+               vec![0xA2, 0x02, 0x0F, 0xF0]);
+
+    // a cycle of includes is an error rather than a stack overflow
+    File::create(dir.join("loop.8o")).unwrap().write_all(b"clear\n:include \"loop.8o\"\n").unwrap();
+    let err = assemble_file(dir.join("loop.8o")).unwrap_err();
+    assert_eq!(err.line, 2);
+    assert!(err.message.ends_with("includes itself"), "{}", err.message);
+}
+
+#[test]
+fn assemble_forward_small_operands() {
+    let rom = assemble("sprite v0 v1 HEIGHT\n\
+                        plane PLANES\n\
+                        scroll-down HEIGHT\n\
+                        :const HEIGHT 8\n\
+                        :const PLANES 3")
+        .unwrap();
+    assert_eq!(rom, vec![0xD0, 0x18, 0xF3, 0x01, 0x00, 0xC8]);
+
+    let err = assemble("sprite v0 v1 SIZE\n:const SIZE 16").unwrap_err();
+    assert_eq!(err.line, 1);
+    assert_eq!(err.message, "value 0x10 does not fit in 4 bits");
+}
+
+#[test]
+fn disassembly_roundtrip() {
+    let roms: &[&[u8]] = &[&[0x00, 0xE0, 0x22, 0x0A, 0x3F, 0x01, 0x12, 0x04, 0x12, 0x08, 0xA2,
+                             0x10, 0xD0, 0x11, 0x00, 0xEE, 0xFF, 0x81],
+                           &[0x12, 0x04, 0x60, 0x01, 0x00, 0xFD, 0x01],
+                           &[0xF0, 0x00, 0x12, 0x34, 0xB2, 0x00, 0x00, 0xFD],
+                           &[0x6A, 0x02, 0xEA, 0x9E, 0x12, 0x00, 0x8A, 0xB6, 0xFA, 0x33,
+                             0xF2, 0x65, 0x00, 0xFD]];
+    for rom in roms {
+        assert_eq!(assemble(&disassemble(rom)).unwrap(), rom.to_vec());
+    }
+}
+
+#[test]
+fn run_assembled() {
+    let rom = assemble("\tv0 := 7\n\
+                        \tv1 := 5\n\
+                        \tadd\n\
+                        \texit\n\
+                        : add\n\
+                        \tv0 += v1\n\
+                        \treturn")
+        .unwrap();
+    let mut c = Chip8::init();
+    c.load_rom(&rom);
+    while c.step().unwrap() != ::StepOutcome::Exited {}
+    assert_eq!(c.v()[0], 12);
+}
@@ -0,0 +1,969 @@
+use std::io::Read;
+use std::fs::File;
+use std::fmt;
+use std::error;
+use std::cmp;
+
+use instruction::Instruction;
+
+mod quirks;
+mod rng;
+mod state;
+mod timing;
+#[cfg(test)]
+mod tests;
+
+pub use self::quirks::{Quirks, MemoryIncrement};
+pub use self::rng::{Rng, XorShift};
+pub use self::state::{StateError, rom_hash};
+pub use self::timing::VIP_MAX_IPF;
+
+const FONTSET: [u8; 80] = [0xF0, 0x90, 0x90, 0x90, 0xF0, 0x20, 0x60, 0x20, 0x20, 0x70, 0xF0, 0x10,
+                           0xF0, 0x80, 0xF0, 0xF0, 0x10, 0xF0, 0x10, 0xF0, 0x90, 0x90, 0xF0, 0x10,
+                           0x10, 0xF0, 0x80, 0xF0, 0x10, 0xF0, 0xF0, 0x80, 0xF0, 0x90, 0xF0, 0xF0,
+                           0x10, 0x20, 0x40, 0x40, 0xF0, 0x90, 0xF0, 0x90, 0xF0, 0xF0, 0x90, 0xF0,
+                           0x10, 0xF0, 0xF0, 0x90, 0xF0, 0x90, 0x90, 0xE0, 0x90, 0xE0, 0x90, 0xE0,
+                           0xF0, 0x80, 0x80, 0x80, 0xF0, 0xE0, 0x90, 0x90, 0x90, 0xE0, 0xF0, 0x80,
+                           0xF0, 0x80, 0xF0, 0xF0, 0x80, 0xF0, 0x80, 0x80];
+
+const SFONTSET: [u8; 160] = [0xF0, 0xF0, 0x90, 0x90, 0x90, 0x90, 0x90, 0x90, 0xF0, 0xF0, 0x20,
+                             0x20, 0x60, 0x60, 0x20, 0x20, 0x20, 0x20, 0x70, 0x70, 0xF0, 0xF0,
+                             0x10, 0x10, 0xF0, 0xF0, 0x80, 0x80, 0xF0, 0xF0, 0xF0, 0xF0, 0x10,
+                             0x10, 0xF0, 0xF0, 0x10, 0x10, 0xF0, 0xF0, 0x90, 0x90, 0x90, 0x90,
+                             0xF0, 0xF0, 0x10, 0x10, 0x10, 0x10, 0xF0, 0xF0, 0x80, 0x80, 0xF0,
+                             0xF0, 0x10, 0x10, 0xF0, 0xF0, 0xF0, 0xF0, 0x80, 0x80, 0xF0, 0xF0,
+                             0x90, 0x90, 0xF0, 0xF0, 0xF0, 0xF0, 0x10, 0x10, 0x20, 0x20, 0x40,
+                             0x40, 0x40, 0x40, 0xF0, 0xF0, 0x90, 0x90, 0xF0, 0xF0, 0x90, 0x90,
+                             0xF0, 0xF0, 0xF0, 0xF0, 0x90, 0x90, 0xF0, 0xF0, 0x10, 0x10, 0xF0,
+                             0xF0, 0xF0, 0xF0, 0x90, 0x90, 0xF0, 0xF0, 0x90, 0x90, 0x90, 0x90,
+                             0xE0, 0xE0, 0x90, 0x90, 0xE0, 0xE0, 0x90, 0x90, 0xE0, 0xE0, 0xF0,
+                             0xF0, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0xF0, 0xF0, 0xE0, 0xE0,
+                             0x90, 0x90, 0x90, 0x90, 0x90, 0x90, 0xE0, 0xE0, 0xF0, 0xF0, 0x80,
+                             0x80, 0xF0, 0xF0, 0x80, 0x80, 0xF0, 0xF0, 0xF0, 0xF0, 0x80, 0x80,
+                             0xF0, 0xF0, 0x80, 0x80, 0x80, 0x80];
+
+/// What happened during a single call to `Chip8::step`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StepOutcome {
+    // an instruction was executed and the PC moved on
+    Executed,
+    // 0xFX0A is blocking until a key is pressed, PC did not move
+    WaitingForKey,
+    // 0x00FD was executed, the interpreter has exited and PC did not move
+    Exited,
+    // 0xDXYN is waiting for the next `tick` with the display wait quirk, PC did not move
+    WaitingForVblank,
+}
+
+/// Errors raised by `Chip8::step`. Every variant carries the PC and the opcode that was being
+/// executed so the caller can decide whether to halt, show a debugger or skip the instruction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Chip8Error {
+    UnknownOpcode { pc: u16, op: u16 },
+    // 0x2NNN with all 16 stack levels in use
+    StackOverflow { pc: u16, op: u16 },
+    // 0x00EE with an empty stack
+    StackUnderflow { pc: u16, op: u16 },
+    // an instruction tried to read or write memory past the end of the address space
+    MemoryOutOfBounds { pc: u16, op: u16, addr: usize },
+    // the PC points outside of memory, either before the fetch or after a jump/return
+    PcOutOfRange { pc: u16, op: u16 },
+}
+
+impl Chip8Error {
+    pub fn pc(&self) -> u16 {
+        match *self {
+            Chip8Error::UnknownOpcode { pc, .. } |
+            Chip8Error::StackOverflow { pc, .. } |
+            Chip8Error::StackUnderflow { pc, .. } |
+            Chip8Error::MemoryOutOfBounds { pc, .. } |
+            Chip8Error::PcOutOfRange { pc, .. } => pc,
+        }
+    }
+
+    pub fn op(&self) -> u16 {
+        match *self {
+            Chip8Error::UnknownOpcode { op, .. } |
+            Chip8Error::StackOverflow { op, .. } |
+            Chip8Error::StackUnderflow { op, .. } |
+            Chip8Error::MemoryOutOfBounds { op, .. } |
+            Chip8Error::PcOutOfRange { op, .. } => op,
+        }
+    }
+}
+
+impl fmt::Display for Chip8Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Chip8Error::UnknownOpcode { pc, op } => {
+                write!(f, "unknown opcode 0x{:04X} at 0x{:04X}", op, pc)
+            }
+            Chip8Error::StackOverflow { pc, op } => {
+                write!(f, "stack overflow by 0x{:04X} at 0x{:04X}", op, pc)
+            }
+            Chip8Error::StackUnderflow { pc, op } => {
+                write!(f, "stack underflow by 0x{:04X} at 0x{:04X}", op, pc)
+            }
+            Chip8Error::MemoryOutOfBounds { pc, op, addr } => {
+                write!(f,
+                       "out of bounds memory access to 0x{:X} by 0x{:04X} at 0x{:04X}",
+                       addr,
+                       op,
+                       pc)
+            }
+            Chip8Error::PcOutOfRange { pc, op } => {
+                write!(f, "PC out of range after 0x{:04X} at 0x{:04X}", op, pc)
+            }
+        }
+    }
+}
+
+impl error::Error for Chip8Error {
+    fn description(&self) -> &str {
+        match *self {
+            Chip8Error::UnknownOpcode { .. } => "unknown opcode",
+            Chip8Error::StackOverflow { .. } => "stack overflow",
+            Chip8Error::StackUnderflow { .. } => "stack underflow",
+            Chip8Error::MemoryOutOfBounds { .. } => "out of bounds memory access",
+            Chip8Error::PcOutOfRange { .. } => "PC out of range",
+        }
+    }
+}
+
+pub struct Chip8 {
+    // 0x000-0x1FF - Chip 8 interpreter (contains font set in emu)
+    // 0x200-0xFFF - Program ROM and work RAM
+    // 0x1000-0xFFFF - Extra program ROM and work RAM, only addressable in XO-CHIP mode
+    memory: [u8; 65536],
+
+    // graphics memory, one byte per pixel. Bit 0 is the first bitplane and bit 1 the second
+    // XO-CHIP bitplane, so every pixel holds a colour index from 0 to 3
+    pub gfx: [u8; 8192],
+    // bitmask of the bitplanes that drawing, clearing and scrolling apply to. Set by 0xFN01
+    plane: u8,
+    // when true, update screen. Set by instructions 0x00E0 (clear screen) and
+    // 0xDXYN (draw sprite)
+    pub draw_flag: bool,
+    pub no_overdraw: bool,
+
+    // interpreter behaviours that differ between CHIP-8, CHIP-48, SCHIP and XO-CHIP
+    pub quirks: Quirks,
+
+    // All registers GP
+    // V[0xF] is a carry flag
+    V: [u8; 16], // registers
+    I: u16, // indexing register
+
+    pc: u16, // program counter
+
+    // counts down at 60Hz
+    delay_timer: u8,
+    pub sound_timer: u8,
+
+    // XO-CHIP audio: a 128 bit 1-bit sample loop set by 0xF002, and the playback pitch set by
+    // 0xFX3A. None until a pattern is loaded, in which case the buzzer is a plain beep
+    pub audio_pattern: Option<[u8; 16]>,
+    pub pitch: u8,
+
+    stack: [u16; 16], // 16 level stack
+    sp: u16, // stack pointer
+
+    // Original mapping
+    // 1 2 3 C
+    // 4 5 6 D
+    // 7 8 9 E
+    // A 0 B F
+    // Map keys to
+    // 1 2 3 4
+    // q w e r
+    // a s d f
+    // z x c v
+    key: [bool; 16], // keypad
+
+    // SCHIP8 extended mode
+    pub extended_mode: bool,
+    user_flags: [u8; 16],
+
+    // source of 0xCXNN's random numbers
+    rng: Box<Rng>,
+
+    // hash of the loaded ROM, save states are only accepted for the same ROM
+    rom_hash: u64,
+
+    // set by tick and cleared by 0xDXYN, which waits for it with the display wait quirk. Not
+    // part of save states, which are taken between frames
+    vblank: bool,
+    // machine cycles left in this frame with the VIP timing quirk, negative when the last
+    // instruction ran over into the next frame. Not part of save states either
+    cycles: i32,
+}
+
+impl Chip8 {
+    pub fn init() -> Chip8 {
+        let mut temp = Chip8 {
+            memory: [0; 65536],
+            gfx: [0; 8192],
+            plane: 1,
+            draw_flag: true,
+            no_overdraw: false,
+            quirks: Quirks::default(),
+            V: [0; 16],
+            I: 0,
+            pc: 0x200,
+            delay_timer: 0,
+            sound_timer: 0,
+            audio_pattern: None,
+            pitch: 64,
+            stack: [0; 16],
+            sp: 16,
+            key: [false; 16],
+            extended_mode: false,
+            user_flags: [0; 16],
+            rng: Box::new(XorShift::from_time()),
+            rom_hash: rom_hash(&[]),
+            vblank: false,
+            cycles: timing::FRAME_CYCLES,
+        };
+        for i in 0..240 {
+            temp.memory[i] = if i < 80 {
+                FONTSET[i]
+            } else {
+                SFONTSET[i - 80]
+            }
+        }
+        temp
+    }
+
+    pub fn load_program(&mut self, filename: String) {
+        let mut f = File::open(filename).unwrap();
+        let mut s = Vec::new();
+        f.read_to_end(&mut s).unwrap();
+        self.load_rom(&s);
+    }
+
+    pub fn load_rom(&mut self, rom: &[u8]) {
+        for (index, &byte) in rom.iter().enumerate() {
+            self.memory[0x200 + index] = byte;
+        }
+        self.rom_hash = rom_hash(rom);
+    }
+
+    /// Loads `rom` over a ROM `old_len` bytes long, clearing whatever the old one left past
+    /// the end of the new one so memory matches a fresh load.
+    pub fn replace_rom(&mut self, old_len: usize, rom: &[u8]) {
+        let end = cmp::min(0x200 + old_len, self.memory.len());
+        for byte in self.memory[0x200..end].iter_mut() {
+            *byte = 0;
+        }
+        self.load_rom(rom);
+    }
+
+    /// Makes 0xCXNN repeatable by seeding the default generator.
+    pub fn seed(&mut self, seed: u64) {
+        self.rng = Box::new(XorShift::new(seed));
+    }
+
+    /// Replaces the random number generator used by 0xCXNN.
+    pub fn set_rng(&mut self, rng: Box<Rng>) {
+        self.rng = rng;
+    }
+
+    pub fn step(&mut self) -> Result<StepOutcome, Chip8Error> {
+        // fetch
+        let pc = self.pc;
+        if pc as usize + 1 >= self.memory_size() {
+            return Err(Chip8Error::PcOutOfRange { pc: pc, op: 0 });
+        }
+        let op = (self.memory[pc as usize] as u16) << 8 | self.memory[(pc + 1) as usize] as u16;
+        // println!("{:04X}: 0x{:04X}", self.pc, op);
+
+        // decode
+        let instruction = match Instruction::decode(op) {
+            Some(i) if !i.is_xo_chip() || self.quirks.xo_chip => i,
+            _ => return Err(Chip8Error::UnknownOpcode { pc: pc, op: op }),
+        };
+
+        if self.quirks.vip_timing && self.cycles <= 0 {
+            return Ok(StepOutcome::WaitingForVblank);
+        }
+        let vx = self.V[((op >> 8) & 0xF) as usize];
+
+        let outcome = try!(self.execute(pc, op, instruction));
+        if self.quirks.vip_timing && outcome != StepOutcome::WaitingForVblank {
+            let skipped = self.pc.wrapping_sub(pc) > 2;
+            self.cycles -= timing::cycles(instruction, vx, skipped);
+        }
+        if self.pc as usize + 1 >= self.memory_size() {
+            return Err(Chip8Error::PcOutOfRange { pc: pc, op: op });
+        }
+        Ok(outcome)
+    }
+
+    fn execute(&mut self,
+               pc: u16,
+               op: u16,
+               instruction: Instruction)
+               -> Result<StepOutcome, Chip8Error> {
+        use instruction::Instruction::*;
+
+        match instruction {
+            ScrollDown(n) => {
+                // scroll down N lines
+                self.scroll(0, n as isize);
+            }
+            ScrollUp(n) => {
+                // scroll up N lines
+                self.scroll(0, -(n as isize));
+            }
+            Clear => {
+                // clear the selected planes
+                let plane = self.plane;
+                for p in self.gfx.iter_mut() {
+                    *p &= !plane;
+                }
+                self.draw_flag = true;
+            }
+            Return => {
+                // return from function
+                if self.sp as usize >= self.stack.len() {
+                    return Err(Chip8Error::StackUnderflow { pc: pc, op: op });
+                }
+                self.pc = self.stack[self.sp as usize];
+                self.sp += 1;
+                try!(self.advance_pc(pc, op, 2));
+                return Ok(StepOutcome::Executed);
+            }
+            ScrollRight => {
+                // scroll 4 pixels right
+                let scroll = self.horizontal_scroll();
+                self.scroll(scroll, 0);
+            }
+            ScrollLeft => {
+                // scroll 4 pixels left
+                let scroll = self.horizontal_scroll();
+                self.scroll(-scroll, 0);
+            }
+            Exit => {
+                // exit interpreter
+                // just hang
+                return Ok(StepOutcome::Exited);
+            }
+            Lores => {
+                // disable extended screen mode
+                self.set_extended_mode(false);
+            }
+            Hires => {
+                // enable extended screen mode
+                self.set_extended_mode(true);
+            }
+            Jump(nnn) => {
+                // jump to address NNN
+                self.pc = nnn;
+                return Ok(StepOutcome::Executed);
+            }
+            Call(nnn) => {
+                // call function at NNN
+                if self.sp == 0 {
+                    return Err(Chip8Error::StackOverflow { pc: pc, op: op });
+                }
+                self.sp -= 1;
+                self.stack[self.sp as usize] = self.pc;
+                self.pc = nnn;
+                return Ok(StepOutcome::Executed);
+            }
+            SkipEqImm(x, nn) => {
+                // skip instruction if VX == NN
+                let skip = self.V[x as usize] == nn;
+                return self.skip_if(pc, op, skip);
+            }
+            SkipNeImm(x, nn) => {
+                // skip instruction if VX != NN
+                let skip = self.V[x as usize] != nn;
+                return self.skip_if(pc, op, skip);
+            }
+            SkipEqReg(x, y) => {
+                // skip instruction if VX == VY
+                let skip = self.V[x as usize] == self.V[y as usize];
+                return self.skip_if(pc, op, skip);
+            }
+            SaveRange(x, y) => {
+                // stores VX to VY in memory starting at address I, I is unchanged
+                let (x, y) = (x as usize, y as usize);
+                let count = if x <= y { y - x } else { x - y } + 1;
+                try!(self.check_memory(pc, op, self.I as usize, count));
+                for i in 0..count {
+                    let r = if x <= y { x + i } else { x - i };
+                    self.memory[self.I as usize + i] = self.V[r];
+                }
+            }
+            LoadRange(x, y) => {
+                // fills VX to VY from memory starting at address I, I is unchanged
+                let (x, y) = (x as usize, y as usize);
+                let count = if x <= y { y - x } else { x - y } + 1;
+                try!(self.check_memory(pc, op, self.I as usize, count));
+                for i in 0..count {
+                    let r = if x <= y { x + i } else { x - i };
+                    self.V[r] = self.memory[self.I as usize + i];
+                }
+            }
+            LoadImm(x, nn) => {
+                // set VX to NN
+                self.V[x as usize] = nn;
+            }
+            AddImm(x, nn) => {
+                // adds NN to VX
+                self.V[x as usize] = self.V[x as usize].wrapping_add(nn);
+            }
+            Move(x, y) => {
+                // sets VX to VY
+                self.V[x as usize] = self.V[y as usize];
+            }
+            Or(x, y) => {
+                // sets VX to VX | VY
+                self.V[x as usize] = self.V[x as usize] | self.V[y as usize];
+                if self.quirks.logic_resets_vf {
+                    self.V[15] = 0;
+                }
+            }
+            And(x, y) => {
+                // sets VX to VX & VY
+                self.V[x as usize] = self.V[x as usize] & self.V[y as usize];
+                if self.quirks.logic_resets_vf {
+                    self.V[15] = 0;
+                }
+            }
+            Xor(x, y) => {
+                // sets VX to VX ^ VY
+                self.V[x as usize] = self.V[x as usize] ^ self.V[y as usize];
+                if self.quirks.logic_resets_vf {
+                    self.V[15] = 0;
+                }
+            }
+            Add(x, y) => {
+                // sets VX to VX + VY. VF set if carry
+                let (v, carry) = self.V[x as usize].overflowing_add(self.V[y as usize]);
+                self.V[x as usize] = v;
+                self.V[15] = if carry {
+                    1
+                } else {
+                    0
+                };
+            }
+            Sub(x, y) => {
+                // sets VX to VX - VY. VF set if no borrow
+                let (v, borrow) = self.V[x as usize].overflowing_sub(self.V[y as usize]);
+                self.V[x as usize] = v;
+                self.V[15] = if borrow {
+                    0
+                } else {
+                    1
+                };
+            }
+            ShiftRight(x, y) => {
+                // sets VX to VX >> 1 (or VY >> 1). VF set to least significant bit before shift
+                let v = self.shift_source(x, y);
+                self.V[x as usize] = v >> 1;
+                self.V[15] = v & 0x01;
+            }
+            SubReverse(x, y) => {
+                // sets VX to VY - VX. VF set if no borrow
+                let (v, borrow) = self.V[y as usize].overflowing_sub(self.V[x as usize]);
+                self.V[x as usize] = v;
+                self.V[15] = if borrow {
+                    0
+                } else {
+                    1
+                };
+            }
+            ShiftLeft(x, y) => {
+                // sets VX to VX << 1 (or VY << 1). VF set to value of most significant bit
+                // before shift
+                let v = self.shift_source(x, y);
+                self.V[x as usize] = v << 1;
+                self.V[15] = v >> 7;
+            }
+            SkipNeReg(x, y) => {
+                // skips next instruction if VX doesn't equal VY
+                let skip = self.V[x as usize] != self.V[y as usize];
+                return self.skip_if(pc, op, skip);
+            }
+            LoadI(nnn) => {
+                // sets I to address NNN
+                self.I = nnn;
+            }
+            JumpOffset(nnn) => {
+                // jumps to address NNN + V0, or XNN + VX
+                let offset = if self.quirks.jump_uses_vx {
+                    self.V[op.x()]
+                } else {
+                    self.V[0]
+                };
+                self.pc = nnn + offset as u16;
+                return Ok(StepOutcome::Executed);
+            }
+            Random(x, nn) => {
+                // sets VX to result of bitwise AND on a random number and NN
+                self.V[x as usize] = nn & self.rng.next_byte();
+            }
+            Draw(_, _, _) if self.quirks.display_wait && !self.vblank => {
+                return Ok(StepOutcome::WaitingForVblank);
+            }
+            Draw(x, y, h) => {
+                self.vblank = false;
+                // XOR-draws sprite at memory location I
+                // Sprites are 8 bits wide. Wraps around the screen, or is clipped at the edges
+                // depending on quirks. If drawing clears a pixel, VF is set to TRUE.
+                // Draws at position VX, VY, N rows high
+                let (width, height) = self.screen_dimens();
+                let x = self.V[x as usize] as usize % width;
+                let y = self.V[y as usize] as usize % height;
+                let clip = self.quirks.clip_sprites;
+                let h = h as usize;
+                let widex = h == 0 && (self.extended_mode || self.quirks.xo_chip);
+                let rows = if h == 0 {
+                    16
+                } else {
+                    h
+                };
+                let sprite_bytes = if widex {
+                    rows * 2
+                } else {
+                    rows
+                };
+                // with both XO-CHIP planes selected the second plane's sprite follows the first
+                let planes: Vec<u8> = (0..2)
+                                          .map(|b| 1 << b)
+                                          .filter(|b| self.plane & b != 0)
+                                          .collect();
+                try!(self.check_memory(pc, op, self.I as usize, sprite_bytes * planes.len()));
+                let mut all_overdraw = true;
+
+                self.V[15] = 0;
+                for (n, &plane) in planes.iter().enumerate() {
+                    let base = self.I as usize + n * sprite_bytes;
+                    for yline in 0..rows {
+                        let (p, shift) = if widex {
+                            (((self.memory[base + (yline * 2)] as u16) << 8) +
+                             self.memory[base + (yline * 2) + 1] as u16,
+                             32768)
+                        } else {
+                            (self.memory[base + yline] as u16, 0b1000_0000)
+                        };
+                        for xline in 0..(if widex {
+                            16
+                        } else {
+                            8
+                        }) {
+                            if clip && (x + xline >= width || y + yline >= height) {
+                                continue;
+                            }
+                            let pos = ((x + xline) % width) + (((y + yline) % height) * width);
+                            if (p & (shift >> xline)) != 0 {
+                                if self.gfx[pos] & plane != 0 {
+                                    self.V[15] = 1;
+                                } else {
+                                    all_overdraw = false;
+                                }
+                                self.gfx[pos] ^= plane;
+                            }
+                        }
+                    }
+                }
+                self.draw_flag = !all_overdraw || self.no_overdraw;
+            }
+            SkipKey(x) => {
+                // skips next instruction if key stored in VX is pressed
+                let skip = self.key[(self.V[x as usize] & 0xF) as usize];
+                return self.skip_if(pc, op, skip);
+            }
+            SkipNotKey(x) => {
+                // skips next instruction if key stores in VX is not pressed
+                let skip = !self.key[(self.V[x as usize] & 0xF) as usize];
+                return self.skip_if(pc, op, skip);
+            }
+            LoadLongI => {
+                // sets I to the 16 bit address NNNN in the following word
+                try!(self.check_memory(pc, op, pc as usize + 2, 2));
+                self.I = (self.memory[pc as usize + 2] as u16) << 8 |
+                         self.memory[pc as usize + 3] as u16;
+                try!(self.advance_pc(pc, op, 4));
+                return Ok(StepOutcome::Executed);
+            }
+            Plane(n) => {
+                // selects the bitplanes N used for drawing
+                self.plane = n;
+            }
+            Audio => {
+                // loads the 16 byte audio pattern at I
+                try!(self.check_memory(pc, op, self.I as usize, 16));
+                let mut pattern = [0; 16];
+                let start = self.I as usize;
+                pattern.copy_from_slice(&self.memory[start..start + 16]);
+                self.audio_pattern = Some(pattern);
+            }
+            GetDelay(x) => {
+                // sets VX to value of delay timer
+                self.V[x as usize] = self.delay_timer;
+            }
+            WaitKey(x) => {
+                // loop on this instruction until a key is pressed, store in VX
+                match self.key.iter().position(|&k| k) {
+                    //pc will only move on once a key has been pressed
+                    Some(i) => self.V[x as usize] = i as u8,
+                    None => return Ok(StepOutcome::WaitingForKey),
+                }
+            }
+            SetDelay(x) => {
+                // set delay timer to VX
+                self.delay_timer = self.V[x as usize];
+            }
+            SetSound(x) => {
+                // set sound timer to VX
+                self.sound_timer = self.V[x as usize];
+            }
+            AddI(x) => {
+                // adds VX to I
+                let (v, carry) = self.I.overflowing_add(self.V[x as usize] as u16);
+                self.I = v;
+                self.V[15] = if carry {
+                    1
+                } else {
+                    0
+                };
+            }
+            Font(x) => {
+                // sets I to the location of the sprite for the character in VX
+                self.I = self.V[x as usize] as u16 * 5;
+            }
+            BigFont(x) => {
+                // sets I to the location of the SCHIP8 sprite for the character in VX
+                self.I = (self.V[x as usize] as u16 * 10) + 80;
+            }
+            Bcd(x) => {
+                // create decimal representation of VX, place hundreds at memory location
+                // I, tens at I+1, and ones at I+2
+                let d = self.V[x as usize];
+                try!(self.check_memory(pc, op, self.I as usize, 3));
+                self.memory[self.I as usize] = d / 100;
+                self.memory[(self.I + 1) as usize] = (d / 10) % 10;
+                self.memory[(self.I + 2) as usize] = (d % 100) % 10;
+            }
+            Pitch(x) => {
+                // sets the audio pattern playback pitch to VX
+                self.pitch = self.V[x as usize];
+            }
+            Save(x) => {
+                // stores V0 to VX in memory starting at addresss I
+                let x = x as usize;
+                try!(self.check_memory(pc, op, self.I as usize, x + 1));
+                for i in 0..x + 1 {
+                    self.memory[self.I as usize + i] = self.V[i];
+                }
+                self.increment_memory_index(x);
+            }
+            Load(x) => {
+                // fills V0 to VX with values from memory starting at address I
+                let x = x as usize;
+                try!(self.check_memory(pc, op, self.I as usize, x + 1));
+                for i in 0..x + 1 {
+                    self.V[i] = self.memory[self.I as usize + i];
+                }
+                self.increment_memory_index(x);
+            }
+            SaveFlags(x) => {
+                // store V0 to VX in user flags
+                for i in 0..x as usize + 1 {
+                    self.user_flags[i] = self.V[i];
+                }
+            }
+            LoadFlags(x) => {
+                // fill V0 to VX from user flags
+                for i in 0..x as usize + 1 {
+                    self.V[i] = self.user_flags[i];
+                }
+            }
+        }
+        // everything that didn't return early is a two byte instruction that falls through
+        try!(self.advance_pc(pc, op, 2));
+        Ok(StepOutcome::Executed)
+    }
+
+    /// Counts down the timers, call it at 60Hz. Also starts a new frame for the display wait
+    /// and VIP timing quirks.
+    #[inline]
+    pub fn tick(&mut self) {
+        self.vblank = true;
+        // unused cycles are lost, an overrun is paid back out of the next frame
+        self.cycles = self.cycles.min(0) + timing::FRAME_CYCLES;
+        if self.delay_timer > 0 {
+            self.delay_timer -= 1;
+        }
+        if self.sound_timer > 0 {
+            self.sound_timer -= 1;
+        }
+    }
+
+    #[inline]
+    pub fn update_keys(&mut self, key: u8, pressed: bool) {
+        self.key[key as usize] = pressed;
+    }
+
+
+    // Read-only views of the machine for frontends and tools
+
+    #[inline]
+    pub fn pc(&self) -> u16 {
+        self.pc
+    }
+
+    #[inline]
+    pub fn i(&self) -> u16 {
+        self.I
+    }
+
+    #[inline]
+    pub fn v(&self) -> &[u8; 16] {
+        &self.V
+    }
+
+    #[inline]
+    pub fn sp(&self) -> u16 {
+        self.sp
+    }
+
+    #[inline]
+    pub fn stack(&self) -> &[u16; 16] {
+        &self.stack
+    }
+
+    #[inline]
+    pub fn delay_timer(&self) -> u8 {
+        self.delay_timer
+    }
+
+    #[inline]
+    pub fn memory(&self) -> &[u8] {
+        &self.memory[..self.memory_size()]
+    }
+
+    /// Hash of the loaded ROM, as used to match save states and movies to it.
+    #[inline]
+    pub fn rom_hash(&self) -> u64 {
+        self.rom_hash
+    }
+
+    /// The opcode at the PC, or None if the PC is outside of memory.
+    pub fn current_opcode(&self) -> Option<u16> {
+        let pc = self.pc as usize;
+        if pc + 1 < self.memory_size() {
+            Some((self.memory[pc] as u16) << 8 | self.memory[pc + 1] as u16)
+        } else {
+            None
+        }
+    }
+
+    // Debugging methods
+
+    #[allow(dead_code)]
+    pub fn reginfo(&self) {
+        print!("PC = {:04X}\nINSTRUCTION = {:04X}\nI = {:04X}\nSP = {}\n",
+               self.pc,
+               (self.memory[self.pc as usize] as u16) << 8 |
+               self.memory[(self.pc + 1) as usize] as u16,
+               self.I,
+               self.sp);
+        for i in 0..16 {
+            print!("V[0x{:02X}] = 0x{:02X}{}",
+                   i,
+                   self.V[i],
+                   if (i + 1) % 4 == 0 {
+                       "\n"
+                   } else {
+                       "\t"
+                   });
+        }
+        print!("\n");
+    }
+
+    #[allow(dead_code)]
+    pub fn dumpgfx(&self) {
+        println!("▁▁▁▁▁▁▁▁▁▁▁▁▁▁▁▁▁▁▁▁▁▁▁▁▁▁▁▁▁▁▁▁▁▁▁▁▁▁▁▁▁▁▁▁▁▁▁▁▁▁▁▁▁▁▁▁▁▁▁▁▁▁▁▁▁▁");
+        for y in 0..32 {
+            print!("|");
+            for x in 0..64 {
+                print!("{}",
+                       if self.gfx[x + (y * 64)] != 0 {
+                           "█"
+                       } else {
+                           "░"
+                       });
+            }
+            println!("|");
+        }
+        println!("▔▔▔▔▔▔▔▔▔▔▔▔▔▔▔▔▔▔▔▔▔▔▔▔▔▔▔▔▔▔▔▔▔▔▔▔▔▔▔▔▔▔▔▔▔▔▔▔▔▔▔▔▔▔▔▔▔▔▔▔▔▔▔▔▔▔");
+    }
+
+    // The value shifted by 0x8XY6/0x8XYE
+    #[inline]
+    fn shift_source(&self, x: u8, y: u8) -> u8 {
+        if self.quirks.shift_uses_vy {
+            self.V[y as usize]
+        } else {
+            self.V[x as usize]
+        }
+    }
+
+    // Moves I on after 0xFX55/0xFX65
+    #[inline]
+    fn increment_memory_index(&mut self, x: usize) {
+        match self.quirks.memory_increment {
+            MemoryIncrement::XPlusOne => self.I = self.I.wrapping_add(x as u16 + 1),
+            MemoryIncrement::X => self.I = self.I.wrapping_add(x as u16),
+            MemoryIncrement::Unchanged => {}
+        }
+    }
+
+    // Returns an error if the `len` bytes starting at `addr` are not all addressable
+    fn check_memory(&self, pc: u16, op: u16, addr: usize, len: usize) -> Result<(), Chip8Error> {
+        if addr + len > self.memory_size() {
+            Err(Chip8Error::MemoryOutOfBounds {
+                pc: pc,
+                op: op,
+                addr: addr + len - 1,
+            })
+        } else {
+            Ok(())
+        }
+    }
+
+    // Moves the PC on by `bytes`, failing rather than wrapping if that passes the end of memory
+    fn advance_pc(&mut self, pc: u16, op: u16, bytes: usize) -> Result<(), Chip8Error> {
+        let next = self.pc as usize + bytes;
+        if next + 1 >= self.memory_size() {
+            return Err(Chip8Error::PcOutOfRange { pc: pc, op: op });
+        }
+        self.pc = next as u16;
+        Ok(())
+    }
+
+    // Moves past a conditional skip, and past the instruction after it when `skip` is set.
+    // The skipped instruction is two words long if it is an XO-CHIP 0xF000 NNNN
+    fn skip_if(&mut self, pc: u16, op: u16, skip: bool) -> Result<StepOutcome, Chip8Error> {
+        try!(self.advance_pc(pc, op, 2));
+        if skip {
+            let next = self.pc as usize;
+            if self.quirks.xo_chip && self.memory[next] == 0xF0 && self.memory[next + 1] == 0x00 {
+                try!(self.advance_pc(pc, op, 4));
+            } else {
+                try!(self.advance_pc(pc, op, 2));
+            }
+        }
+        Ok(StepOutcome::Executed)
+    }
+
+    // Moves the selected planes by dx pixels right and dy pixels down, filling with blank pixels
+    fn scroll(&mut self, dx: isize, dy: isize) {
+        let (width, height) = self.screen_dimens();
+        let plane = self.plane;
+        let old = self.gfx;
+        for y in 0..height {
+            for x in 0..width {
+                let (sx, sy) = (x as isize - dx, y as isize - dy);
+                let src = if sx >= 0 && sy >= 0 && (sx as usize) < width && (sy as usize) < height {
+                    old[sx as usize + sy as usize * width]
+                } else {
+                    0
+                };
+                let pos = x + y * width;
+                self.gfx[pos] = (old[pos] & !plane) | (src & plane);
+            }
+        }
+        self.draw_flag = true;
+    }
+
+    // SCHIP scrolls half as far in low resolution mode, XO-CHIP always scrolls by 4 pixels
+    #[inline]
+    fn horizontal_scroll(&self) -> isize {
+        if self.extended_mode || self.quirks.xo_chip {
+            4
+        } else {
+            2
+        }
+    }
+
+    fn set_extended_mode(&mut self, extended: bool) {
+        self.extended_mode = extended;
+        if self.quirks.xo_chip {
+            // XO-CHIP clears the display when switching resolution
+            self.gfx = [0; 8192];
+            self.draw_flag = true;
+        }
+    }
+
+    // Utils
+    #[inline]
+    pub fn memory_size(&self) -> usize {
+        if self.quirks.xo_chip {
+            65536
+        } else {
+            4096
+        }
+    }
+
+    #[inline]
+    pub fn screen_dimens(&self) -> (usize, usize) {
+        (if self.extended_mode {
+            (128, 64)
+        } else {
+            (64, 32)
+        })
+    }
+}
+
+pub trait ByteManip {
+    fn high_byte(&self) -> u8;
+    fn low_byte(&self) -> u8;
+    fn nibble(&self, position: u8) -> u8;
+
+    fn x(&self) -> usize; //shorthand for the positions of X and Y register indices in Chip8 opcodes
+    fn y(&self) -> usize;
+    fn nnn(&self) -> u16; //shorthand for NNN memory location pattern in opcodes
+}
+
+impl ByteManip for u16 {
+    #[inline]
+    fn high_byte(&self) -> u8 {
+        (self >> 8) as u8
+    }
+
+    #[inline]
+    fn low_byte(&self) -> u8 {
+        (self & 0x00FF) as u8
+    }
+
+    #[inline]
+    fn nibble(&self, position: u8) -> u8 {
+        match position {
+            1 => (self >> 12) as u8,
+            2 => ((self >> 8) & 0x000F as u16) as u8,
+            3 => ((self >> 4) & 0x000F as u16) as u8,
+            4 => (self & 0x000F as u16) as u8,
+            _ => panic!("Out of range nibble position {}", position),
+        }
+    }
+
+    #[inline]
+    fn x(&self) -> usize {
+        self.nibble(2) as usize
+    }
+
+    #[inline]
+    fn y(&self) -> usize {
+        self.nibble(3) as usize
+    }
+
+    #[inline]
+    fn nnn(&self) -> u16 {
+        self & 0x0FFF as u16
+    }
+}
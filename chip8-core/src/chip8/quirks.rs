@@ -0,0 +1,102 @@
+// Behavioural differences between the CHIP-8 interpreters that ROMs were written for.
+// Each preset matches the interpreter most ROMs of that family expect.
+
+/// How 0xFX55/0xFX65 leave the I register once they are done.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MemoryIncrement {
+    // I += X + 1, the original COSMAC VIP behaviour
+    XPlusOne,
+    // I += X, CHIP-48 and SCHIP 1.0
+    X,
+    // I is left untouched, SCHIP 1.1
+    Unchanged,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Quirks {
+    // 0x8XY6/0x8XYE shift VY into VX instead of shifting VX in place
+    pub shift_uses_vy: bool,
+    // what 0xFX55/0xFX65 do to I
+    pub memory_increment: MemoryIncrement,
+    // 0xBNNN jumps to XNN + VX instead of NNN + V0
+    pub jump_uses_vx: bool,
+    // sprites are clipped at the screen edges instead of wrapping around
+    pub clip_sprites: bool,
+    // 0x8XY1/0x8XY2/0x8XY3 reset VF to 0
+    pub logic_resets_vf: bool,
+    // enables the XO-CHIP opcodes, the 64K address space and the second bitplane
+    pub xo_chip: bool,
+    // 0xDXYN waits for the next 60Hz frame before drawing, like the VIP's display interrupt.
+    // Off in every preset, frontends turn it on with --display-wait
+    pub display_wait: bool,
+    // instructions cost what they did on the COSMAC VIP and each frame only runs as many as
+    // fit in its cycle budget, see timing.rs. Off in every preset like display_wait
+    pub vip_timing: bool,
+}
+
+impl Quirks {
+    pub fn chip8() -> Quirks {
+        Quirks {
+            shift_uses_vy: true,
+            memory_increment: MemoryIncrement::XPlusOne,
+            jump_uses_vx: false,
+            clip_sprites: true,
+            logic_resets_vf: true,
+            xo_chip: false,
+            display_wait: false,
+            vip_timing: false,
+        }
+    }
+
+    pub fn chip48() -> Quirks {
+        Quirks {
+            shift_uses_vy: false,
+            memory_increment: MemoryIncrement::X,
+            jump_uses_vx: true,
+            clip_sprites: true,
+            logic_resets_vf: false,
+            xo_chip: false,
+            display_wait: false,
+            vip_timing: false,
+        }
+    }
+
+    pub fn schip10() -> Quirks {
+        Quirks::chip48()
+    }
+
+    pub fn schip11() -> Quirks {
+        Quirks { memory_increment: MemoryIncrement::Unchanged, ..Quirks::chip48() }
+    }
+
+    pub fn xochip() -> Quirks {
+        Quirks {
+            shift_uses_vy: true,
+            memory_increment: MemoryIncrement::XPlusOne,
+            jump_uses_vx: false,
+            clip_sprites: false,
+            logic_resets_vf: false,
+            xo_chip: true,
+            display_wait: false,
+            vip_timing: false,
+        }
+    }
+
+    /// Looks up a preset by the name used on the command line.
+    pub fn from_name(name: &str) -> Option<Quirks> {
+        match &*name.to_lowercase() {
+            "chip8" | "chip-8" | "vip" => Some(Quirks::chip8()),
+            "chip48" | "chip-48" => Some(Quirks::chip48()),
+            "schip10" | "schip1.0" => Some(Quirks::schip10()),
+            "schip11" | "schip1.1" | "schip" => Some(Quirks::schip11()),
+            "xochip" | "xo-chip" => Some(Quirks::xochip()),
+            _ => None,
+        }
+    }
+}
+
+impl Default for Quirks {
+    fn default() -> Quirks {
+        Quirks::schip11()
+    }
+}
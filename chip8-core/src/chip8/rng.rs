@@ -0,0 +1,65 @@
+// Random numbers for 0xCXNN. The generator belongs to the machine so a run can be repeated
+// from a seed, and its state is part of save states.
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// A source of random bytes for `Chip8`. Implementations must be fully described by a `u64`
+/// so the state can be saved and restored.
+pub trait Rng {
+    fn next_byte(&mut self) -> u8;
+    fn state(&self) -> u64;
+    fn set_state(&mut self, state: u64);
+}
+
+/// xorshift64*, the default generator.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct XorShift {
+    state: u64,
+}
+
+impl XorShift {
+    pub fn new(seed: u64) -> XorShift {
+        // splitmix64 spreads small seeds over the state, which must never be zero
+        let mut z = seed.wrapping_add(0x9E3779B97F4A7C15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+        z ^= z >> 31;
+        XorShift {
+            state: if z == 0 {
+                0x9E3779B97F4A7C15
+            } else {
+                z
+            },
+        }
+    }
+
+    /// Seeded from the clock, for runs that don't need to be repeatable.
+    pub fn from_time() -> XorShift {
+        let seed = SystemTime::now()
+                       .duration_since(UNIX_EPOCH)
+                       .map(|d| d.as_secs() ^ (d.subsec_nanos() as u64) << 32)
+                       .unwrap_or(0);
+        XorShift::new(seed)
+    }
+}
+
+impl Rng for XorShift {
+    fn next_byte(&mut self) -> u8 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        (self.state.wrapping_mul(0x2545F4914F6CDD1D) >> 56) as u8
+    }
+
+    fn state(&self) -> u64 {
+        self.state
+    }
+
+    fn set_state(&mut self, state: u64) {
+        self.state = if state == 0 {
+            0x9E3779B97F4A7C15
+        } else {
+            state
+        };
+    }
+}
@@ -0,0 +1,237 @@
+// Save states: a versioned binary snapshot of the whole machine.
+//
+// Layout, all integers little endian:
+//   "C8ST" magic, u8 version, u64 ROM hash, then the machine fields in declaration order.
+// Quirks and frontend options are configuration rather than state and are not included.
+// Version 2 added the random number generator state at the end, version 1 states still load
+// and leave the generator alone.
+
+use std::fmt;
+use std::error;
+
+use super::Chip8;
+
+const MAGIC: &'static [u8; 4] = b"C8ST";
+const VERSION: u8 = 2;
+
+const HEADER_LEN: usize = 4 + 1 + 8;
+const BODY_LEN_V1: usize = 65536 + 8192 + 1 + 16 + 2 + 2 + 1 + 1 + 1 + 16 + 1 + 16 * 2 + 2 + 16 +
+                           1 + 16;
+const BODY_LEN: usize = BODY_LEN_V1 + 8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StateError {
+    // the data does not start with the save state magic
+    BadMagic,
+    UnsupportedVersion(u8),
+    // the state was saved while a different ROM was loaded
+    RomMismatch { expected: u64, found: u64 },
+    Truncated,
+    // a register or pointer is out of range, so the state can't have been saved by a machine
+    Corrupt,
+}
+
+impl fmt::Display for StateError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            StateError::BadMagic => write!(f, "not a save state"),
+            StateError::UnsupportedVersion(v) => write!(f, "unsupported save state version {}", v),
+            StateError::RomMismatch { expected, found } => {
+                write!(f,
+                       "save state is for ROM {:016X}, but ROM {:016X} is loaded",
+                       found,
+                       expected)
+            }
+            StateError::Truncated => write!(f, "save state is truncated"),
+            StateError::Corrupt => write!(f, "save state is corrupt"),
+        }
+    }
+}
+
+impl error::Error for StateError {
+    fn description(&self) -> &str {
+        match *self {
+            StateError::BadMagic => "not a save state",
+            StateError::UnsupportedVersion(_) => "unsupported save state version",
+            StateError::RomMismatch { .. } => "save state is for a different ROM",
+            StateError::Truncated => "save state is truncated",
+            StateError::Corrupt => "save state is corrupt",
+        }
+    }
+}
+
+/// FNV-1a hash used to tie save states to the ROM they were made with.
+pub fn rom_hash(rom: &[u8]) -> u64 {
+    rom.iter().fold(0xcbf29ce484222325, |h, &b| (h ^ b as u64).wrapping_mul(0x100000001b3))
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, n: usize) -> &'a [u8] {
+        let slice = &self.data[self.pos..self.pos + n];
+        self.pos += n;
+        slice
+    }
+
+    fn u8(&mut self) -> u8 {
+        self.bytes(1)[0]
+    }
+
+    fn u16(&mut self) -> u16 {
+        let b = self.bytes(2);
+        b[0] as u16 | (b[1] as u16) << 8
+    }
+
+    fn u64(&mut self) -> u64 {
+        self.bytes(8).iter().rev().fold(0, |acc, &b| acc << 8 | b as u64)
+    }
+}
+
+fn push_u16(out: &mut Vec<u8>, v: u16) {
+    out.push(v as u8);
+    out.push((v >> 8) as u8);
+}
+
+fn push_u64(out: &mut Vec<u8>, v: u64) {
+    for i in 0..8 {
+        out.push((v >> (i * 8)) as u8);
+    }
+}
+
+impl Chip8 {
+    pub fn save_state(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(HEADER_LEN + BODY_LEN);
+        out.extend_from_slice(MAGIC);
+        out.push(VERSION);
+        push_u64(&mut out, self.rom_hash);
+
+        out.extend_from_slice(&self.memory);
+        out.extend_from_slice(&self.gfx);
+        out.push(self.plane);
+        out.extend_from_slice(&self.V);
+        push_u16(&mut out, self.I);
+        push_u16(&mut out, self.pc);
+        out.push(self.delay_timer);
+        out.push(self.sound_timer);
+        match self.audio_pattern {
+            Some(ref pattern) => {
+                out.push(1);
+                out.extend_from_slice(pattern);
+            }
+            None => {
+                out.push(0);
+                out.extend_from_slice(&[0; 16]);
+            }
+        }
+        out.push(self.pitch);
+        for &s in self.stack.iter() {
+            push_u16(&mut out, s);
+        }
+        push_u16(&mut out, self.sp);
+        for &k in self.key.iter() {
+            out.push(k as u8);
+        }
+        out.push(self.extended_mode as u8);
+        out.extend_from_slice(&self.user_flags);
+        push_u64(&mut out, self.rng.state());
+        out
+    }
+
+    /// Restores a state made by `save_state`. The machine is left untouched if the state is
+    /// rejected.
+    pub fn load_state(&mut self, data: &[u8]) -> Result<(), StateError> {
+        if data.len() < HEADER_LEN {
+            return Err(StateError::Truncated);
+        }
+        let mut r = Reader {
+            data: data,
+            pos: 0,
+        };
+        if r.bytes(4) != MAGIC {
+            return Err(StateError::BadMagic);
+        }
+        let version = r.u8();
+        let body_len = match version {
+            1 => BODY_LEN_V1,
+            VERSION => BODY_LEN,
+            _ => return Err(StateError::UnsupportedVersion(version)),
+        };
+        let hash = r.u64();
+        if hash != self.rom_hash {
+            return Err(StateError::RomMismatch {
+                expected: self.rom_hash,
+                found: hash,
+            });
+        }
+        if data.len() < HEADER_LEN + body_len {
+            return Err(StateError::Truncated);
+        }
+
+        let memory = r.bytes(65536);
+        let gfx = r.bytes(8192);
+        let plane = r.u8();
+        let v = r.bytes(16);
+        let i = r.u16();
+        let pc = r.u16();
+        let delay_timer = r.u8();
+        let sound_timer = r.u8();
+        let has_pattern = r.u8() != 0;
+        let mut pattern = [0; 16];
+        pattern.copy_from_slice(r.bytes(16));
+        let pitch = r.u8();
+        let mut stack = [0; 16];
+        for s in stack.iter_mut() {
+            *s = r.u16();
+        }
+        let sp = r.u16();
+        let mut key = [false; 16];
+        for k in key.iter_mut() {
+            *k = r.u8() != 0;
+        }
+        let extended_mode = r.u8() != 0;
+        let user_flags = r.bytes(16);
+        let rng_state = if version >= 2 {
+            Some(r.u64())
+        } else {
+            None
+        };
+
+        // the file may have been edited or damaged, so check sp against the stack length, the pc
+        // and return addresses in use against memory and the plane mask, so nothing restored
+        // can index out of bounds later
+        let memory_size = self.memory_size();
+        if plane > 3 || sp as usize > stack.len() || pc as usize + 1 >= memory_size ||
+           stack[sp as usize..].iter().any(|&s| s as usize + 1 >= memory_size) {
+            return Err(StateError::Corrupt);
+        }
+
+        self.memory.copy_from_slice(memory);
+        self.gfx.copy_from_slice(gfx);
+        self.plane = plane;
+        self.V.copy_from_slice(v);
+        self.I = i;
+        self.pc = pc;
+        self.delay_timer = delay_timer;
+        self.sound_timer = sound_timer;
+        self.audio_pattern = if has_pattern {
+            Some(pattern)
+        } else {
+            None
+        };
+        self.pitch = pitch;
+        self.stack = stack;
+        self.sp = sp;
+        self.key = key;
+        self.extended_mode = extended_mode;
+        self.user_flags.copy_from_slice(user_flags);
+        if let Some(state) = rng_state {
+            self.rng.set_state(state);
+        }
+        self.draw_flag = true;
+        Ok(())
+    }
+}
@@ -0,0 +1,396 @@
+use super::*;
+
+#[test]
+fn bitmanip_high() {
+    assert_eq!(0xABCD.high_byte(), 0xAB);
+}
+
+#[test]
+fn bitmanip_low() {
+    assert_eq!(0xABCD.low_byte(), 0xCD);
+}
+
+#[test]
+fn bitmanip_nibble() {
+    let n = 0xABCD;
+    assert_eq!(n.nibble(1), 0xA);
+    assert_eq!(n.nibble(2), 0xB);
+    assert_eq!(n.nibble(3), 0xC);
+    assert_eq!(n.nibble(4), 0xD);
+}
+
+fn memset(c: &mut Chip8, location: usize, values: &[u16]) {
+    for (ind, mem) in values.iter().enumerate() {
+        c.memory[location + ind * 2] = (mem >> 8) as u8;
+        c.memory[location + (ind * 2) + 1] = (mem & 0x00FF) as u8;
+    }
+}
+
+#[test]
+fn chip8test() {
+    let mut c = Chip8::init();
+    assert_eq!(c.pc, 0x200);
+    memset(&mut c, 0x200, &[0x1204]); //jump to 0x204
+    c.reginfo();
+    c.step().unwrap(); //0x200
+    c.reginfo();
+    assert_eq!(c.pc, 0x204);
+    memset(&mut c, 0x190, &[0x6C0F, 0x3C0F, 0x0000, 0x00EE]); //load 0xF into V0, skip next instruction (0x0000) if V0 == 0xF, return from function.
+    memset(&mut c, 0x204, &[0x2190]); //call function at 0x190
+    c.step().unwrap(); //0x204
+    c.reginfo();
+    assert_eq!(c.pc, 0x190);
+    assert_eq!(c.sp, 15);
+    assert_eq!(c.stack[15], 0x204);
+    c.step().unwrap(); //0x190
+    c.reginfo();
+    assert_eq!(c.V[0xC], 0xF);
+    c.step().unwrap(); //0x192: will panic with invalid instruction 0x0000 if this instruction fails
+    c.reginfo();
+    c.step().unwrap(); //0x196
+    assert_eq!(c.sp, 16);
+    assert_eq!(c.pc, 0x206);
+}
+
+#[test]
+fn chip8drawtest() {
+    let mut c = Chip8::init();
+    memset(&mut c,
+           0x200,
+           &[0x6003, 0xF029, 0xD125, 0x00E0, 0x600A, 0xF029, 0xD125]);
+    c.step().unwrap(); //0x6003
+    assert_eq!(c.V[0], 0x3);
+    c.step().unwrap(); //0xF029
+    assert_eq!(c.I, 0xF);
+    c.step().unwrap(); //0xD125
+    c.dumpgfx();
+    c.step().unwrap(); //0x00E0
+    c.step().unwrap(); //0x600A
+    c.step().unwrap(); //0xF029
+    c.step().unwrap(); //0xD125
+    c.dumpgfx();
+    assert!(c.draw_flag);
+}
+
+#[test]
+fn chip8errortest() {
+    let mut c = Chip8::init();
+    memset(&mut c, 0x200, &[0x00EE]); //return with an empty stack
+    assert_eq!(c.step(),
+               Err(Chip8Error::StackUnderflow {
+                   pc: 0x200,
+                   op: 0x00EE,
+               }));
+
+    let mut c = Chip8::init();
+    memset(&mut c, 0x200, &[0x0000]);
+    assert_eq!(c.step(),
+               Err(Chip8Error::UnknownOpcode {
+                   pc: 0x200,
+                   op: 0x0000,
+               }));
+    assert_eq!(c.pc, 0x200);
+
+    let mut c = Chip8::init();
+    memset(&mut c, 0x200, &[0xAFFF, 0xF255]); //set I to 0xFFF and store V0-V2 past the end
+    c.step().unwrap();
+    assert_eq!(c.step(),
+               Err(Chip8Error::MemoryOutOfBounds {
+                   pc: 0x202,
+                   op: 0xF255,
+                   addr: 0x1001,
+               }));
+
+    let mut c = Chip8::init();
+    memset(&mut c, 0x200, &[0x1FFF]); //jump to the last byte of memory
+    assert_eq!(c.step(),
+               Err(Chip8Error::PcOutOfRange {
+                   pc: 0x200,
+                   op: 0x1FFF,
+               }));
+}
+
+#[test]
+fn chip8outcometest() {
+    let mut c = Chip8::init();
+    memset(&mut c, 0x200, &[0xF30A, 0x00FD]);
+    assert_eq!(c.step(), Ok(StepOutcome::WaitingForKey));
+    assert_eq!(c.pc, 0x200);
+    c.update_keys(0xB, true);
+    assert_eq!(c.step(), Ok(StepOutcome::Executed));
+    assert_eq!(c.V[3], 0xB);
+    assert_eq!(c.step(), Ok(StepOutcome::Exited));
+    assert_eq!(c.pc, 0x202);
+}
+
+#[test]
+fn chip8quirkstest() {
+    let program = [0x6001, 0x6281, 0x8126, 0xA300, 0xF155];
+
+    let mut c = Chip8::init();
+    c.quirks = Quirks::chip8();
+    memset(&mut c, 0x200, &program);
+    for _ in 0..program.len() {
+        c.step().unwrap();
+    }
+    assert_eq!(c.V[1], 0x40); //VY shifted into VX
+    assert_eq!(c.V[0xF], 1);
+    assert_eq!(c.I, 0x302);
+
+    let mut c = Chip8::init();
+    c.quirks = Quirks::schip11();
+    memset(&mut c, 0x200, &program);
+    for _ in 0..program.len() {
+        c.step().unwrap();
+    }
+    assert_eq!(c.V[1], 0x00); //VX shifted in place
+    assert_eq!(c.V[0xF], 0);
+    assert_eq!(c.I, 0x300);
+}
+
+#[test]
+fn chip8cliptest() {
+    let mut c = Chip8::init();
+    c.quirks = Quirks::schip11();
+    memset(&mut c, 0x200, &[0x603E, 0x6100, 0xA000, 0xD015]); //draw "0" at x=62
+    for _ in 0..4 {
+        c.step().unwrap();
+    }
+    assert_eq!(c.gfx[62], 1);
+    assert_eq!(c.gfx[0], 0); //clipped at the right edge
+
+    let mut c = Chip8::init();
+    c.quirks = Quirks::xochip();
+    memset(&mut c, 0x200, &[0x603E, 0x6100, 0xA000, 0xD015]);
+    for _ in 0..4 {
+        c.step().unwrap();
+    }
+    assert_eq!(c.gfx[62], 1);
+    assert_eq!(c.gfx[0], 1); //wrapped around to the left edge
+}
+
+#[test]
+fn xochiptest() {
+    let mut c = Chip8::init();
+    c.quirks = Quirks::xochip();
+    memset(&mut c,
+           0x200,
+           &[0xF000, 0x1234, 0x6001, 0x6102, 0x5012, 0x3001, 0xF000, 0x0300, 0xF201]);
+    c.step().unwrap(); //0xF000 0x1234
+    assert_eq!(c.I, 0x1234);
+    assert_eq!(c.pc, 0x204);
+    c.step().unwrap();
+    c.step().unwrap();
+    c.step().unwrap(); //save V0 - V1 at 0x1234
+    assert_eq!(c.memory[0x1234], 1);
+    assert_eq!(c.memory[0x1235], 2);
+    assert_eq!(c.I, 0x1234);
+    c.step().unwrap(); //skips both words of the long load
+    assert_eq!(c.pc, 0x210);
+    c.step().unwrap(); //select plane 2
+    assert_eq!(c.plane, 2);
+
+    memset(&mut c, 0x212, &[0xA000, 0x6000, 0xD001, 0x00D1]);
+    c.step().unwrap();
+    c.step().unwrap();
+    c.step().unwrap(); //draw the top row of "0" on plane 2 only
+    assert_eq!(c.gfx[0], 2);
+    c.step().unwrap(); //scroll plane 2 up, row 0 is gone
+    assert_eq!(c.gfx[0], 0);
+}
+
+#[test]
+fn xochipendofmemorytest() {
+    // the last instruction in memory can't fall through, skip or load a long address
+    for &(pc, ops) in &[(0xFFFEu16, &[0x6001][..]),
+                        (0xFFFC, &[0x3000, 0x6001][..]),
+                        (0xFFFC, &[0xF000, 0x1234][..])] {
+        let mut c = Chip8::init();
+        c.quirks = Quirks::xochip();
+        memset(&mut c, pc as usize, ops);
+        c.pc = pc;
+        assert_eq!(c.step(),
+                   Err(Chip8Error::PcOutOfRange {
+                       pc: pc,
+                       op: ops[0],
+                   }));
+    }
+
+    // nor can a return to a call made from there
+    let mut c = Chip8::init();
+    c.quirks = Quirks::xochip();
+    memset(&mut c, 0x200, &[0x00EE]);
+    c.sp -= 1;
+    c.stack[c.sp as usize] = 0xFFFE;
+    assert_eq!(c.step(),
+               Err(Chip8Error::PcOutOfRange {
+                   pc: 0x200,
+                   op: 0x00EE,
+               }));
+}
+
+#[test]
+fn xochipgatetest() {
+    let mut c = Chip8::init();
+    memset(&mut c, 0x200, &[0xF201]);
+    assert_eq!(c.step(),
+               Err(Chip8Error::UnknownOpcode {
+                   pc: 0x200,
+                   op: 0xF201,
+               }));
+}
+
+#[test]
+fn savestatetest() {
+    let mut c = Chip8::init();
+    c.load_rom(&[0x60, 0x2A, 0xA2, 0x34, 0x22, 0x08, 0x00, 0x00, 0x00, 0xE0]);
+    c.step().unwrap();
+    c.step().unwrap();
+    c.step().unwrap();
+    c.tick();
+    let state = c.save_state();
+
+    let mut d = Chip8::init();
+    d.load_rom(&[0x60, 0x2A, 0xA2, 0x34, 0x22, 0x08, 0x00, 0x00, 0x00, 0xE0]);
+    d.load_state(&state).unwrap();
+    assert_eq!(d.V[0], 0x2A);
+    assert_eq!(d.I, 0x234);
+    assert_eq!(d.pc, 0x208);
+    assert_eq!(d.sp, 15);
+    assert_eq!(d.stack[15], 0x204);
+    assert_eq!(d.save_state(), state);
+
+    let mut e = Chip8::init();
+    e.load_rom(&[0x12, 0x00]);
+    match e.load_state(&state) {
+        Err(StateError::RomMismatch { .. }) => {}
+        other => panic!("expected a ROM mismatch, got {:?}", other),
+    }
+    assert_eq!(e.load_state(&state[..20]), Err(StateError::RomMismatch {
+        expected: e.rom_hash,
+        found: c.rom_hash,
+    }));
+    assert_eq!(d.load_state(&state[..state.len() - 1]), Err(StateError::Truncated));
+    assert_eq!(d.load_state(b"nope"), Err(StateError::Truncated));
+}
+
+#[test]
+fn replaceromtest() {
+    let mut c = Chip8::init();
+    c.load_rom(&[0x60, 0x2A, 0xA2, 0x34, 0x12, 0x04]);
+    c.replace_rom(6, &[0x61, 0x01]);
+    assert_eq!(&c.memory()[0x200..0x208], &[0x61, 0x01, 0, 0, 0, 0, 0, 0]);
+
+    let mut fresh = Chip8::init();
+    fresh.load_rom(&[0x61, 0x01]);
+    assert!(c.memory() == fresh.memory());
+}
+
+#[test]
+fn savestatecorrupttest() {
+    let mut c = Chip8::init();
+    c.load_rom(&[0x22, 0x04, 0x00, 0x00, 0x12, 0x04]);
+    c.step().unwrap();
+    let state = c.save_state();
+
+    // offsets of the plane, pc, the stack and sp, after the header, memory and display
+    let plane = 13 + 65536 + 8192;
+    let pc = plane + 1 + 16 + 2;
+    let stack = pc + 2 + 1 + 1 + 1 + 16 + 1;
+    let sp = stack + 16 * 2;
+    for &(offset, value) in &[(plane, 4), (pc + 1, 0x10), (sp, 40), (stack + 15 * 2 + 1, 0xFF)] {
+        let mut corrupt = state.clone();
+        corrupt[offset] = value;
+        assert_eq!(c.load_state(&corrupt), Err(StateError::Corrupt));
+    }
+    // the machine is untouched, so it steps on from where it was
+    assert_eq!(c.save_state(), state);
+    assert_eq!(c.sp, 15);
+    c.step().unwrap();
+}
+
+#[test]
+fn seededrandomtest() {
+    // v0 := random 0xFF eight times
+    let rom: Vec<u8> = (0..8).flat_map(|x| vec![0xC0 | x, 0xFF]).collect();
+    let run = |seed: u64| {
+        let mut c = Chip8::init();
+        c.seed(seed);
+        c.load_rom(&rom);
+        for _ in 0..8 {
+            c.step().unwrap();
+        }
+        c.V
+    };
+    assert_eq!(run(1), run(1));
+    assert!(run(1) != run(2));
+
+    // the generator state is saved, so a restored machine draws the same numbers
+    let mut c = Chip8::init();
+    c.seed(7);
+    c.load_rom(&rom);
+    c.step().unwrap();
+    let state = c.save_state();
+    let mut d = Chip8::init();
+    d.load_rom(&rom);
+    d.load_state(&state).unwrap();
+    for _ in 0..7 {
+        c.step().unwrap();
+        d.step().unwrap();
+    }
+    assert_eq!(c.V, d.V);
+}
+
+#[test]
+fn displaywaittest() {
+    // two draws in a row only manage one per frame with the quirk
+    let mut c = Chip8::init();
+    c.quirks.display_wait = true;
+    memset(&mut c, 0x200, &[0xD005, 0xD005, 0x00FD]);
+    assert_eq!(c.step(), Ok(StepOutcome::WaitingForVblank));
+    assert_eq!(c.pc, 0x200);
+    c.tick();
+    assert_eq!(c.step(), Ok(StepOutcome::Executed));
+    assert_eq!(c.step(), Ok(StepOutcome::WaitingForVblank));
+    assert_eq!(c.pc, 0x202);
+    c.tick();
+    assert_eq!(c.step(), Ok(StepOutcome::Executed));
+    assert_eq!(c.step(), Ok(StepOutcome::Exited));
+
+    // without it both draw straight away
+    let mut c = Chip8::init();
+    memset(&mut c, 0x200, &[0xD005, 0xD005]);
+    assert_eq!(c.step(), Ok(StepOutcome::Executed));
+    assert_eq!(c.step(), Ok(StepOutcome::Executed));
+}
+
+#[test]
+fn viptimingtest() {
+    // v0 += 1 in a loop, each pass costs 10 + 12 cycles
+    let mut c = Chip8::init();
+    c.quirks.vip_timing = true;
+    memset(&mut c, 0x200, &[0x7001, 0x1200]);
+    let mut run_frame = |c: &mut Chip8| {
+        let mut executed = 0;
+        while c.step() == Ok(StepOutcome::Executed) {
+            executed += 1;
+        }
+        c.tick();
+        executed
+    };
+    // 2572 cycles run 117 passes, the last jump running 2 cycles into the next frame
+    assert_eq!(run_frame(&mut c), 234);
+    assert_eq!(c.V[0], 117);
+    // which still fits 117 in the 2570 left
+    assert_eq!(run_frame(&mut c), 234);
+
+    // a clear takes more than a frame's cycles, so the next frame starts 530 in debt and only
+    // runs the load and 170 jumps instead of 214
+    let mut c = Chip8::init();
+    c.quirks.vip_timing = true;
+    memset(&mut c, 0x200, &[0x00E0, 0x6000, 0x1204]);
+    assert_eq!(c.step(), Ok(StepOutcome::Executed));
+    assert_eq!(c.step(), Ok(StepOutcome::WaitingForVblank));
+    c.tick();
+    assert_eq!(run_frame(&mut c), 171);
+}
@@ -0,0 +1,70 @@
+// COSMAC VIP instruction timing, for the vip_timing quirk.
+//
+// The VIP's 1802 runs at 1.7609MHz with 8 clocks to a machine cycle, 3668 machine cycles per
+// 60Hz frame. The CDP1861 takes 1024 of those for display DMA and the interrupt routine that
+// sets it up and counts down the timers takes about 72 more, leaving the CHIP-8 interpreter
+// about 2572. Costs below are machine cycles through the original interpreter's routines,
+// including its fetch and decode. Data dependent routines like the sprite drawing are
+// approximated from their loop counts.
+
+use instruction::Instruction;
+
+/// Machine cycles left for the interpreter in each 60Hz frame.
+pub const FRAME_CYCLES: i32 = 3668 - 1024 - 72;
+
+// The cheapest instruction, 0x6XNN
+const MIN_CYCLES: i32 = 6;
+
+/// Most instructions that fit in a frame, for frontends to use as their instructions per frame
+/// so that the cycle budget rather than the count ends each frame.
+pub const VIP_MAX_IPF: usize = (FRAME_CYCLES / MIN_CYCLES) as usize + 1;
+
+/// Machine cycles `instruction` took, given VX before it ran and whether it skipped the next
+/// instruction.
+pub fn cycles(instruction: Instruction, vx: u8, skipped: bool) -> i32 {
+    use instruction::Instruction::*;
+
+    let skip = if skipped {
+        4
+    } else {
+        0
+    };
+    match instruction {
+        // the clear loop writes all 256 bytes of display memory
+        Clear => 24 + 3078,
+        Return => 10,
+        Jump(_) => 12,
+        Call(_) => 26,
+        SkipEqImm(..) | SkipNeImm(..) => 10 + skip,
+        SkipEqReg(..) | SkipNeReg(..) => 14 + skip,
+        LoadImm(..) => MIN_CYCLES,
+        AddImm(..) => 10,
+        // the arithmetic instructions all go through the same self-modifying routine
+        Move(..) | Or(..) | And(..) | Xor(..) | Add(..) | Sub(..) | ShiftRight(..) |
+        SubReverse(..) | ShiftLeft(..) => 44,
+        LoadI(_) => 12,
+        JumpOffset(_) => 22,
+        Random(..) => 36,
+        // sprites on a byte boundary skip the shifting into a second byte
+        Draw(_, _, rows) => {
+            let per_row = if vx % 8 == 0 {
+                46
+            } else {
+                66
+            };
+            68 + rows as i32 * per_row
+        }
+        SkipKey(_) | SkipNotKey(_) => 14 + skip,
+        GetDelay(_) => 10,
+        // each poll while waiting for a key
+        WaitKey(_) => 18,
+        SetDelay(_) | SetSound(_) => 10,
+        AddI(_) => 16,
+        Font(_) => 16,
+        // the conversion counts down each digit
+        Bcd(_) => 80 + 16 * (vx / 100 + vx / 10 % 10 + vx % 10) as i32,
+        Save(x) | Load(x) => 14 + 14 * (x as i32 + 1),
+        // SCHIP and XO-CHIP instructions never ran on a VIP, charge a typical cost
+        _ => 20,
+    }
+}
@@ -0,0 +1,527 @@
+// Breakpoints, watchpoints and stepping on top of Chip8::step, plus the text commands the
+// frontends expose for them.
+
+use std::fmt;
+
+use chip8::{Chip8, Chip8Error, StepOutcome};
+use instruction::Instruction;
+
+#[cfg(test)]
+mod tests;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Breakpoint {
+    Address(u16),
+    // stops on any opcode where `op & mask == value`
+    Opcode { value: u16, mask: u16 },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Watchpoint {
+    // inclusive range of addresses
+    Memory { start: usize, end: usize },
+    Register(u8),
+}
+
+/// Why the debugger paused execution.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stop {
+    // the user asked for it
+    Paused,
+    // the numbered breakpoint matched the instruction at the PC, which has not run yet
+    Breakpoint(usize),
+    // the instruction at `pc` changed what the numbered watchpoint covers
+    Watchpoint { id: usize, pc: u16 },
+    // a step, step over or step out finished
+    Step,
+}
+
+/// A parsed debugger command.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Command {
+    Pause,
+    Continue,
+    Step,
+    StepOver,
+    StepOut,
+    Break(Breakpoint),
+    Watch(Watchpoint),
+    Delete(usize),
+    List,
+    Registers,
+    Stack,
+    Memory { start: usize, len: usize },
+    Help,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    Paused,
+    Running,
+    Step,
+    // run until the PC returns to `pc` with the stack back at `sp`
+    StepOver { pc: u16, sp: u16 },
+    // run until a return pops the stack above `sp`
+    StepOut { sp: u16 },
+}
+
+const HELP: &'static str = "\
+pause (p)                 pause execution
+continue (c)              resume execution
+step (s)                  run one instruction
+next (n)                  run one instruction, stepping over calls
+finish (f)                run until the current subroutine returns
+break <addr> (b)          break when the PC reaches addr
+break op <pattern>        break on opcodes matching pattern, x matches any nibble, e.g. Dxy0
+watch <addr>[-<addr>] (w) break when memory in the range changes
+watch v<x>                break when a register changes
+delete <id> (d)           remove a breakpoint or watchpoint
+list (l)                  show breakpoints and watchpoints
+regs (r)                  show registers
+stack                     show the stack
+mem <addr> [len] (m)      dump memory
+";
+
+pub struct Debugger {
+    mode: Mode,
+    // breakpoints and watchpoints share ids so one delete command handles both
+    breakpoints: Vec<(usize, Breakpoint)>,
+    watchpoints: Vec<(usize, Watchpoint)>,
+    next_id: usize,
+    // set on resume so the breakpoint we are stopped on doesn't fire again straight away
+    resumed: bool,
+    stop: Option<Stop>,
+}
+
+fn parse_number(text: &str) -> Result<usize, String> {
+    let parsed = if text.starts_with("0x") || text.starts_with("0X") {
+        usize::from_str_radix(&text[2..], 16)
+    } else {
+        text.parse()
+    };
+    parsed.map_err(|_| format!("invalid number {}", text))
+}
+
+fn parse_register(text: &str) -> Option<u8> {
+    let lower = text.to_lowercase();
+    if lower.len() == 2 && lower.starts_with('v') {
+        u8::from_str_radix(&lower[1..], 16).ok()
+    } else {
+        None
+    }
+}
+
+// Parses an opcode pattern like D01x into a value and mask
+fn parse_pattern(text: &str) -> Result<Breakpoint, String> {
+    let digits = text.trim_left_matches("0x");
+    if digits.len() != 4 {
+        return Err(format!("opcode pattern {} must be four nibbles", text));
+    }
+    let mut value = 0;
+    let mut mask = 0;
+    for c in digits.chars() {
+        value <<= 4;
+        mask <<= 4;
+        match c.to_digit(16) {
+            Some(d) => {
+                value |= d as u16;
+                mask |= 0xF;
+            }
+            None if "xXyYnN?".contains(c) => {}
+            None => return Err(format!("invalid opcode pattern {}", text)),
+        }
+    }
+    Ok(Breakpoint::Opcode {
+        value: value,
+        mask: mask,
+    })
+}
+
+impl Command {
+    pub fn parse(line: &str) -> Result<Command, String> {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let arg = |n: usize| -> Result<&str, String> {
+            words.get(n).map(|w| *w).ok_or(format!("{} needs more arguments", words[0]))
+        };
+        if words.is_empty() {
+            return Err(String::from("empty command"));
+        }
+        Ok(match words[0] {
+            "pause" | "p" => Command::Pause,
+            "continue" | "c" => Command::Continue,
+            "step" | "s" => Command::Step,
+            "next" | "n" => Command::StepOver,
+            "finish" | "f" => Command::StepOut,
+            "break" | "b" => {
+                if try!(arg(1)) == "op" {
+                    Command::Break(try!(parse_pattern(try!(arg(2)))))
+                } else {
+                    Command::Break(Breakpoint::Address(try!(parse_number(try!(arg(1)))) as u16))
+                }
+            }
+            "watch" | "w" => {
+                let target = try!(arg(1));
+                match parse_register(target) {
+                    Some(x) => Command::Watch(Watchpoint::Register(x)),
+                    None => {
+                        let mut range = target.splitn(2, '-');
+                        let start = try!(parse_number(range.next().unwrap()));
+                        let end = match range.next() {
+                            Some(end) => try!(parse_number(end)),
+                            None => start,
+                        };
+                        if end < start {
+                            return Err(format!("empty range {}", target));
+                        }
+                        Command::Watch(Watchpoint::Memory {
+                            start: start,
+                            end: end,
+                        })
+                    }
+                }
+            }
+            "delete" | "d" => Command::Delete(try!(parse_number(try!(arg(1))))),
+            "list" | "l" => Command::List,
+            "regs" | "r" => Command::Registers,
+            "stack" => Command::Stack,
+            "mem" | "m" => {
+                Command::Memory {
+                    start: try!(parse_number(try!(arg(1)))),
+                    len: match words.get(2) {
+                        Some(len) => try!(parse_number(len)),
+                        None => 16,
+                    },
+                }
+            }
+            "help" | "h" | "?" => Command::Help,
+            other => return Err(format!("unknown command {}, try help", other)),
+        })
+    }
+}
+
+impl fmt::Display for Breakpoint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Breakpoint::Address(addr) => write!(f, "address 0x{:03X}", addr),
+            Breakpoint::Opcode { value, mask } => {
+                try!(write!(f, "opcode "));
+                for shift in [12, 8, 4, 0].iter() {
+                    if (mask >> *shift) & 0xF == 0 {
+                        try!(write!(f, "x"));
+                    } else {
+                        try!(write!(f, "{:X}", (value >> *shift) & 0xF));
+                    }
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl fmt::Display for Watchpoint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Watchpoint::Memory { start, end } if start == end => {
+                write!(f, "memory 0x{:03X}", start)
+            }
+            Watchpoint::Memory { start, end } => write!(f, "memory 0x{:03X}-0x{:03X}", start, end),
+            Watchpoint::Register(x) => write!(f, "register v{:x}", x),
+        }
+    }
+}
+
+impl Breakpoint {
+    fn matches(&self, c8: &Chip8) -> bool {
+        match *self {
+            Breakpoint::Address(addr) => c8.pc() == addr,
+            Breakpoint::Opcode { value, mask } => {
+                c8.current_opcode().map(|op| op & mask == value).unwrap_or(false)
+            }
+        }
+    }
+}
+
+impl Watchpoint {
+    // The bytes this watchpoint covers, compared before and after each instruction
+    fn snapshot(&self, c8: &Chip8) -> Vec<u8> {
+        match *self {
+            Watchpoint::Memory { start, end } => {
+                let memory = c8.memory();
+                let end = (end + 1).min(memory.len());
+                memory[start.min(end)..end].to_vec()
+            }
+            Watchpoint::Register(x) => vec![c8.v()[x as usize & 0xF]],
+        }
+    }
+}
+
+impl Debugger {
+    /// A debugger that starts with execution paused.
+    pub fn new() -> Debugger {
+        Debugger {
+            mode: Mode::Paused,
+            breakpoints: Vec::new(),
+            watchpoints: Vec::new(),
+            next_id: 1,
+            resumed: false,
+            stop: Some(Stop::Paused),
+        }
+    }
+
+    #[inline]
+    pub fn is_paused(&self) -> bool {
+        self.mode == Mode::Paused
+    }
+
+    pub fn pause(&mut self) {
+        if !self.is_paused() {
+            self.halt(Stop::Paused);
+        }
+    }
+
+    pub fn resume(&mut self) {
+        self.start(Mode::Running);
+    }
+
+    pub fn step_into(&mut self) {
+        self.start(Mode::Step);
+    }
+
+    /// Steps one instruction, running a called subroutine to completion.
+    pub fn step_over(&mut self, c8: &Chip8) {
+        match c8.current_opcode().and_then(Instruction::decode) {
+            Some(Instruction::Call(_)) => {
+                self.start(Mode::StepOver {
+                    // a call in the last word of XO-CHIP memory has nowhere to return to
+                    pc: c8.pc().wrapping_add(2),
+                    sp: c8.sp(),
+                })
+            }
+            _ => self.start(Mode::Step),
+        }
+    }
+
+    /// Runs until the current subroutine returns.
+    pub fn step_out(&mut self, c8: &Chip8) {
+        self.start(Mode::StepOut { sp: c8.sp() });
+    }
+
+    /// Returns why execution last stopped, once.
+    pub fn take_stop(&mut self) -> Option<Stop> {
+        self.stop.take()
+    }
+
+    pub fn add_breakpoint(&mut self, breakpoint: Breakpoint) -> usize {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.breakpoints.push((id, breakpoint));
+        id
+    }
+
+    pub fn add_watchpoint(&mut self, watchpoint: Watchpoint) -> usize {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.watchpoints.push((id, watchpoint));
+        id
+    }
+
+    /// Removes the breakpoint or watchpoint with this id, returns false if there was none.
+    pub fn delete(&mut self, id: usize) -> bool {
+        let before = self.breakpoints.len() + self.watchpoints.len();
+        self.breakpoints.retain(|&(i, _)| i != id);
+        self.watchpoints.retain(|&(i, _)| i != id);
+        before != self.breakpoints.len() + self.watchpoints.len()
+    }
+
+    fn start(&mut self, mode: Mode) {
+        self.mode = mode;
+        self.resumed = true;
+        self.stop = None;
+    }
+
+    fn halt(&mut self, stop: Stop) {
+        self.mode = Mode::Paused;
+        self.stop = Some(stop);
+    }
+
+    /// Runs one instruction unless the debugger is paused or a breakpoint stops it first.
+    /// Returns None when nothing was executed.
+    pub fn step(&mut self, c8: &mut Chip8) -> Result<Option<StepOutcome>, Chip8Error> {
+        if self.is_paused() {
+            return Ok(None);
+        }
+        if !self.resumed {
+            let hit = self.breakpoints.iter().find(|&&(_, ref b)| b.matches(c8)).map(|&(id, _)| id);
+            if let Some(id) = hit {
+                self.halt(Stop::Breakpoint(id));
+                return Ok(None);
+            }
+        }
+        self.resumed = false;
+
+        let pc = c8.pc();
+        let before: Vec<Vec<u8>> = self.watchpoints
+                                       .iter()
+                                       .map(|&(_, ref w)| w.snapshot(c8))
+                                       .collect();
+        let outcome = match c8.step() {
+            Ok(outcome) => outcome,
+            Err(e) => {
+                self.halt(Stop::Paused);
+                return Err(e);
+            }
+        };
+
+        let changed = self.watchpoints
+                          .iter()
+                          .zip(before.iter())
+                          .find(|&(&(_, ref w), old)| w.snapshot(c8) != *old)
+                          .map(|(&(id, _), _)| id);
+        if let Some(id) = changed {
+            self.halt(Stop::Watchpoint { id: id, pc: pc });
+            return Ok(Some(outcome));
+        }
+
+        let done = match self.mode {
+            Mode::Step => true,
+            Mode::StepOver { pc, sp } => c8.pc() == pc && c8.sp() == sp,
+            Mode::StepOut { sp } => c8.sp() > sp,
+            Mode::Running | Mode::Paused => false,
+        };
+        if done || outcome == StepOutcome::Exited {
+            self.halt(Stop::Step);
+        }
+        Ok(Some(outcome))
+    }
+
+    /// Runs a command against the machine and returns the text to show the user.
+    pub fn execute(&mut self, c8: &Chip8, command: Command) -> String {
+        match command {
+            Command::Pause => {
+                self.pause();
+                String::new()
+            }
+            Command::Continue => {
+                self.resume();
+                String::new()
+            }
+            Command::Step => {
+                self.step_into();
+                String::new()
+            }
+            Command::StepOver => {
+                self.step_over(c8);
+                String::new()
+            }
+            Command::StepOut => {
+                self.step_out(c8);
+                String::new()
+            }
+            Command::Break(breakpoint) => {
+                let id = self.add_breakpoint(breakpoint);
+                format!("Breakpoint {} on {}\n", id, breakpoint)
+            }
+            Command::Watch(watchpoint) => {
+                let id = self.add_watchpoint(watchpoint);
+                format!("Watchpoint {} on {}\n", id, watchpoint)
+            }
+            Command::Delete(id) => {
+                if self.delete(id) {
+                    format!("Deleted {}\n", id)
+                } else {
+                    format!("No breakpoint or watchpoint {}\n", id)
+                }
+            }
+            Command::List => {
+                let mut out = String::new();
+                for &(id, ref b) in &self.breakpoints {
+                    out.push_str(&format!("{}: break on {}\n", id, b));
+                }
+                for &(id, ref w) in &self.watchpoints {
+                    out.push_str(&format!("{}: watch {}\n", id, w));
+                }
+                if out.is_empty() {
+                    out.push_str("No breakpoints or watchpoints\n");
+                }
+                out
+            }
+            Command::Registers => registers(c8),
+            Command::Stack => stack(c8),
+            Command::Memory { start, len } => memory(c8, start, len),
+            Command::Help => String::from(HELP),
+        }
+    }
+
+    /// Describes a stop, with the instruction the PC now points at.
+    pub fn describe(&self, c8: &Chip8, stop: Stop) -> String {
+        let reason = match stop {
+            Stop::Paused => String::from("Paused"),
+            Stop::Breakpoint(id) => format!("Breakpoint {}", id),
+            Stop::Watchpoint { id, pc } => format!("Watchpoint {} hit by 0x{:03X}", id, pc),
+            Stop::Step => String::from("Stepped"),
+        };
+        format!("{} at {}\n", reason, current_instruction(c8))
+    }
+}
+
+fn current_instruction(c8: &Chip8) -> String {
+    match c8.current_opcode() {
+        Some(op) => {
+            match Instruction::decode(op) {
+                Some(i) => format!("0x{:03X}: {:04X}  {}", c8.pc(), op, i),
+                None => format!("0x{:03X}: {:04X}  (unknown)", c8.pc(), op),
+            }
+        }
+        None => format!("0x{:03X}: (out of memory)", c8.pc()),
+    }
+}
+
+fn registers(c8: &Chip8) -> String {
+    let mut out = format!("{}\nI  = 0x{:03X}  SP = {}  DT = {}  ST = {}\n",
+                          current_instruction(c8),
+                          c8.i(),
+                          c8.sp(),
+                          c8.delay_timer(),
+                          c8.sound_timer);
+    for (i, v) in c8.v().iter().enumerate() {
+        out.push_str(&format!("v{:x} = 0x{:02X}{}",
+                              i,
+                              v,
+                              if (i + 1) % 8 == 0 {
+                                  "\n"
+                              } else {
+                                  "  "
+                              }));
+    }
+    out
+}
+
+// The stack grows down from 16, so live entries are sp..16 with the newest first
+fn stack(c8: &Chip8) -> String {
+    let sp = c8.sp() as usize;
+    let mut out = format!("SP = {}\n", sp);
+    for (depth, addr) in c8.stack()[sp.min(16)..].iter().enumerate() {
+        out.push_str(&format!("#{} return to 0x{:03X}\n", depth, addr));
+    }
+    out
+}
+
+fn memory(c8: &Chip8, start: usize, len: usize) -> String {
+    let memory = c8.memory();
+    let end = start.saturating_add(len).min(memory.len());
+    let mut out = String::new();
+    let mut addr = start;
+    while addr < end {
+        let row = &memory[addr..(addr + 16).min(end)];
+        out.push_str(&format!("0x{:03X}:", addr));
+        for byte in row {
+            out.push_str(&format!(" {:02X}", byte));
+        }
+        out.push('\n');
+        addr += 16;
+    }
+    if out.is_empty() {
+        out.push_str("Address out of range\n");
+    }
+    out
+}
@@ -0,0 +1,167 @@
+use super::*;
+use asm::assemble;
+
+fn machine(source: &str) -> Chip8 {
+    let mut c8 = Chip8::init();
+    c8.load_rom(&assemble(source).unwrap());
+    c8
+}
+
+// Steps until the debugger pauses, at most `limit` times
+fn run(debugger: &mut Debugger, c8: &mut Chip8, limit: usize) -> Option<Stop> {
+    for _ in 0..limit {
+        debugger.step(c8).unwrap();
+        if debugger.is_paused() {
+            return debugger.take_stop();
+        }
+    }
+    None
+}
+
+const PROGRAM: &'static str = "\
+    v0 := 1\n\
+    double\n\
+    v2 := 3\n\
+: loop\n\
+    jump loop\n\
+: double\n\
+    v0 += v0\n\
+    i := 0x300\n\
+    save v0\n\
+    return";
+
+#[test]
+fn parse_commands() {
+    assert_eq!(Command::parse("b 0x204"),
+               Ok(Command::Break(Breakpoint::Address(0x204))));
+    assert_eq!(Command::parse("break op Dxy0"),
+               Ok(Command::Break(Breakpoint::Opcode {
+                   value: 0xD000,
+                   mask: 0xF00F,
+               })));
+    assert_eq!(Command::parse("watch 0x300-0x30F"),
+               Ok(Command::Watch(Watchpoint::Memory {
+                   start: 0x300,
+                   end: 0x30F,
+               })));
+    assert_eq!(Command::parse("w vA"), Ok(Command::Watch(Watchpoint::Register(0xA))));
+    assert_eq!(Command::parse("mem 512"),
+               Ok(Command::Memory {
+                   start: 512,
+                   len: 16,
+               }));
+    assert!(Command::parse("break").is_err());
+    assert!(Command::parse("frobnicate").is_err());
+}
+
+#[test]
+fn starts_paused() {
+    let mut c8 = machine(PROGRAM);
+    let mut debugger = Debugger::new();
+    assert_eq!(debugger.step(&mut c8), Ok(None));
+    assert_eq!(c8.pc(), 0x200);
+}
+
+#[test]
+fn breakpoints() {
+    let mut c8 = machine(PROGRAM);
+    let mut debugger = Debugger::new();
+    let address = debugger.add_breakpoint(Breakpoint::Address(0x204));
+    let opcode = debugger.add_breakpoint(Breakpoint::Opcode {
+        value: 0x00EE,
+        mask: 0xFFFF,
+    });
+
+    debugger.resume();
+    assert_eq!(run(&mut debugger, &mut c8, 100), Some(Stop::Breakpoint(opcode)));
+    assert_eq!(c8.pc(), 0x20E);
+    debugger.resume();
+    assert_eq!(run(&mut debugger, &mut c8, 100), Some(Stop::Breakpoint(address)));
+    assert_eq!(c8.pc(), 0x204);
+    assert_eq!(c8.v()[0], 2);
+
+    assert!(debugger.delete(address));
+    assert!(!debugger.delete(address));
+}
+
+#[test]
+fn watchpoints() {
+    let mut c8 = machine(PROGRAM);
+    let mut debugger = Debugger::new();
+    let register = debugger.add_watchpoint(Watchpoint::Register(0));
+    let memory = debugger.add_watchpoint(Watchpoint::Memory {
+        start: 0x300,
+        end: 0x300,
+    });
+
+    debugger.resume();
+    assert_eq!(run(&mut debugger, &mut c8, 100),
+               Some(Stop::Watchpoint {
+                   id: register,
+                   pc: 0x200,
+               }));
+    debugger.resume();
+    assert_eq!(run(&mut debugger, &mut c8, 100),
+               Some(Stop::Watchpoint {
+                   id: register,
+                   pc: 0x208,
+               }));
+    debugger.resume();
+    assert_eq!(run(&mut debugger, &mut c8, 100),
+               Some(Stop::Watchpoint {
+                   id: memory,
+                   pc: 0x20C,
+               }));
+    assert_eq!(c8.memory()[0x300], 2);
+}
+
+#[test]
+fn stepping() {
+    let mut c8 = machine(PROGRAM);
+    let mut debugger = Debugger::new();
+
+    debugger.step_into();
+    assert_eq!(run(&mut debugger, &mut c8, 10), Some(Stop::Step));
+    assert_eq!(c8.pc(), 0x202);
+
+    // step over the call to double
+    debugger.step_over(&c8);
+    assert_eq!(run(&mut debugger, &mut c8, 10), Some(Stop::Step));
+    assert_eq!(c8.pc(), 0x204);
+    assert_eq!(c8.v()[0], 2);
+
+    // step into it this time, then out again
+    let mut c8 = machine(PROGRAM);
+    debugger.step_into();
+    run(&mut debugger, &mut c8, 10);
+    debugger.step_into();
+    run(&mut debugger, &mut c8, 10);
+    assert_eq!(c8.pc(), 0x208);
+    assert_eq!(c8.sp(), 15);
+    assert_eq!(debugger.execute(&c8, Command::Stack),
+               "SP = 15\n#0 return to 0x202\n");
+    debugger.step_out(&c8);
+    assert_eq!(run(&mut debugger, &mut c8, 10), Some(Stop::Step));
+    assert_eq!(c8.pc(), 0x204);
+    assert_eq!(c8.sp(), 16);
+}
+
+#[test]
+fn step_over_end_of_memory() {
+    let mut c8 = Chip8::init();
+    c8.quirks = ::Quirks::xochip();
+    // a state with `call 0xABC` in the last word of memory and the pc on it
+    let mut state = c8.save_state();
+    let pc = 13 + 65536 + 8192 + 1 + 16 + 2;
+    state[13 + 0xFFFE] = 0x2A;
+    state[13 + 0xFFFF] = 0xBC;
+    state[pc] = 0xFE;
+    state[pc + 1] = 0xFF;
+    c8.load_state(&state).unwrap();
+
+    let mut debugger = Debugger::new();
+    debugger.step_over(&c8);
+    debugger.step(&mut c8).unwrap();
+    assert!(!debugger.is_paused());
+    assert_eq!(c8.pc(), 0xABC);
+}
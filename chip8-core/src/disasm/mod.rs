@@ -0,0 +1,169 @@
+// Disassembler producing Octo assembly that reassembles to the same bytes.
+//
+// Code is found by walking from 0x200, following jumps and calls and both outcomes of every
+// skip. Anything the walk does not reach is written out as data bytes.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use instruction::Instruction;
+
+#[cfg(test)]
+mod tests;
+
+// where ROMs are loaded
+const ORIGIN: usize = 0x200;
+// data bytes per line
+const DATA_PER_LINE: usize = 8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Label {
+    Subroutine,
+    Jump,
+}
+
+fn fetch(rom: &[u8], addr: usize) -> Option<u16> {
+    let offset = addr.wrapping_sub(ORIGIN);
+    if addr >= ORIGIN && offset + 1 < rom.len() {
+        Some((rom[offset] as u16) << 8 | rom[offset + 1] as u16)
+    } else {
+        None
+    }
+}
+
+// Decodes the instruction at addr, if all of its bytes are inside the ROM
+fn decode_at(rom: &[u8], addr: usize) -> Option<Instruction> {
+    fetch(rom, addr).and_then(Instruction::decode).and_then(|i| {
+        if i.len() == 4 && fetch(rom, addr + 2).is_none() {
+            None
+        } else {
+            Some(i)
+        }
+    })
+}
+
+/// Finds the addresses of every reachable instruction, and the targets of jumps and calls.
+fn walk(rom: &[u8]) -> (BTreeSet<usize>, BTreeMap<usize, Label>) {
+    let mut code = BTreeSet::new();
+    let mut labels = BTreeMap::new();
+    let mut pending = vec![ORIGIN];
+
+    while let Some(addr) = pending.pop() {
+        if code.contains(&addr) {
+            continue;
+        }
+        let instruction = match decode_at(rom, addr) {
+            Some(i) => i,
+            None => continue,
+        };
+        code.insert(addr);
+        let next = addr + instruction.len() as usize;
+
+        match instruction {
+            Instruction::Jump(nnn) => {
+                labels.entry(nnn as usize).or_insert(Label::Jump);
+                pending.push(nnn as usize);
+            }
+            Instruction::Call(nnn) => {
+                labels.insert(nnn as usize, Label::Subroutine);
+                pending.push(nnn as usize);
+                pending.push(next);
+            }
+            // the target of jump0 depends on V0, so the walk can't follow it
+            Instruction::Return | Instruction::Exit | Instruction::JumpOffset(_) => {}
+            i if i.is_skip() => {
+                pending.push(next);
+                let skipped = decode_at(rom, next).map(|i| i.len()).unwrap_or(2);
+                pending.push(next + skipped as usize);
+            }
+            _ => pending.push(next),
+        }
+    }
+    (code, labels)
+}
+
+fn label_name(addr: usize, label: Label) -> String {
+    match label {
+        Label::Subroutine => format!("sub_{:04X}", addr),
+        Label::Jump => format!("label_{:04X}", addr),
+    }
+}
+
+/// Disassembles a ROM loaded at 0x200 into Octo assembly.
+pub fn disassemble(rom: &[u8]) -> String {
+    let (code, labels) = walk(rom);
+
+    // lay the program out first, overlapping instructions can't both be emitted as code and
+    // only boundaries between emitted lines can carry a label
+    let mut lines = Vec::new();
+    let mut addr = ORIGIN;
+    let end = ORIGIN + rom.len();
+    while addr < end {
+        if code.contains(&addr) {
+            let instruction = decode_at(rom, addr).unwrap();
+            lines.push((addr, Some(instruction)));
+            addr += instruction.len() as usize;
+        } else {
+            lines.push((addr, None));
+            addr += 1;
+        }
+    }
+    let boundaries: BTreeSet<usize> = lines.iter().map(|&(addr, _)| addr).collect();
+    let name = |target: u16| -> Option<String> {
+        let target = target as usize;
+        match labels.get(&target) {
+            Some(&label) if boundaries.contains(&target) => Some(label_name(target, label)),
+            _ => None,
+        }
+    };
+
+    let mut out = String::new();
+    let mut data_run = 0;
+    for &(addr, instruction) in &lines {
+        if let Some(&label) = labels.get(&addr) {
+            if data_run > 0 {
+                out.push('\n');
+                data_run = 0;
+            }
+            out.push_str(&format!(": {}\n", label_name(addr, label)));
+        }
+        match instruction {
+            Some(i) => {
+                if data_run > 0 {
+                    out.push('\n');
+                    data_run = 0;
+                }
+                let text = match i {
+                    Instruction::Jump(nnn) => {
+                        match name(nnn) {
+                            Some(label) => format!("jump {}", label),
+                            None => format!("{}", i),
+                        }
+                    }
+                    Instruction::Call(nnn) => name(nnn).unwrap_or(format!("{}", i)),
+                    Instruction::LoadLongI => {
+                        format!("i := long 0x{:04X}", fetch(rom, addr + 2).unwrap())
+                    }
+                    _ => format!("{}", i),
+                };
+                out.push_str(&format!("\t{}\n", text));
+            }
+            None => {
+                out.push_str(if data_run == 0 {
+                    "\t"
+                } else {
+                    " "
+                });
+                out.push_str(&format!("0x{:02X}", rom[addr - ORIGIN]));
+                data_run += 1;
+                if data_run == DATA_PER_LINE {
+                    out.push('\n');
+                    data_run = 0;
+                }
+            }
+        }
+    }
+    if data_run > 0 {
+        out.push('\n');
+    }
+    out
+}
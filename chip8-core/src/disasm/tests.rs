@@ -0,0 +1,45 @@
+use super::*;
+
+#[test]
+fn disassemble_code_and_data() {
+    let rom = [0x00, 0xE0, // 0x200 clear
+               0x22, 0x0A, // 0x202 call 0x20A
+               0x3F, 0x01, // 0x204 skip if vF == 1
+               0x12, 0x04, // 0x206 jump 0x204
+               0x12, 0x08, // 0x208 jump to itself
+               0xA2, 0x10, // 0x20A i := 0x210
+               0xD0, 0x11, // 0x20C sprite v0 v1 1
+               0x00, 0xEE, // 0x20E return
+               0xFF, 0x81]; // 0x210 sprite data
+    assert_eq!(disassemble(&rom),
+               "\tclear\n\
+                \tsub_020A\n\
+                : label_0204\n\
+                \tif vf != 0x01 then\n\
+                \tjump label_0204\n\
+                : label_0208\n\
+                \tjump label_0208\n\
+                : sub_020A\n\
+                \ti := 0x210\n\
+                \tsprite v0 v1 1\n\
+                \treturn\n\
+                \t0xFF 0x81\n");
+}
+
+#[test]
+fn disassemble_unreachable_as_data() {
+    // the jump skips over a valid instruction, which is left as data
+    let rom = [0x12, 0x04, 0x60, 0x01, 0x00, 0xFD, 0x01];
+    assert_eq!(disassemble(&rom),
+               "\tjump label_0204\n\
+                \t0x60 0x01\n\
+                : label_0204\n\
+                \texit\n\
+                \t0x01\n");
+}
+
+#[test]
+fn disassemble_long_load() {
+    let rom = [0xF0, 0x00, 0x12, 0x34, 0x00, 0xFD];
+    assert_eq!(disassemble(&rom), "\ti := long 0x1234\n\texit\n");
+}
@@ -0,0 +1,304 @@
+// GDB remote serial protocol stub. It only deals in bytes, the frontend owns the socket and
+// feeds whatever it reads to `receive`, sending back what that and `step` return.
+//
+// Registers are v0-vf (8 bit), i and pc (16 bit, little endian), sp, dt and st (8 bit), described
+// to gdb through target.xml.
+
+use std::collections::HashMap;
+
+use chip8::{Chip8, Chip8Error, StepOutcome};
+use debugger::{Breakpoint, Debugger, Stop};
+
+#[cfg(test)]
+mod tests;
+
+// signals reported in stop replies
+const SIGINT: u8 = 2;
+const SIGILL: u8 = 4;
+const SIGTRAP: u8 = 5;
+const SIGSEGV: u8 = 11;
+
+const REGISTER_COUNT: usize = 21;
+
+pub struct GdbStub {
+    debugger: Debugger,
+    // bytes received that don't make a full packet yet
+    buffer: Vec<u8>,
+    // breakpoint ids in the debugger, by address
+    breakpoints: HashMap<u16, usize>,
+    // gdb is waiting for a stop reply after a continue or step
+    running: bool,
+}
+
+fn checksum(data: &[u8]) -> u8 {
+    data.iter().fold(0u8, |sum, &b| sum.wrapping_add(b))
+}
+
+/// Frames a reply as `$data#checksum`.
+fn packet(data: &str) -> Vec<u8> {
+    format!("${}#{:02x}", data, checksum(data.as_bytes())).into_bytes()
+}
+
+fn hex_bytes(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn parse_hex(text: &str) -> Option<usize> {
+    usize::from_str_radix(text, 16).ok()
+}
+
+fn target_xml() -> String {
+    let mut xml = String::from("<?xml version=\"1.0\"?><!DOCTYPE target SYSTEM \"gdb-target.dtd\">\
+                                <target version=\"1.0\"><feature name=\"org.chip8.cpu\">");
+    for x in 0..16 {
+        xml.push_str(&format!("<reg name=\"v{:x}\" bitsize=\"8\" type=\"uint8\"/>", x));
+    }
+    xml.push_str("<reg name=\"i\" bitsize=\"16\" type=\"data_ptr\"/>\
+                  <reg name=\"pc\" bitsize=\"16\" type=\"code_ptr\"/>\
+                  <reg name=\"sp\" bitsize=\"8\" type=\"uint8\"/>\
+                  <reg name=\"dt\" bitsize=\"8\" type=\"uint8\"/>\
+                  <reg name=\"st\" bitsize=\"8\" type=\"uint8\"/>\
+                  </feature></target>");
+    xml
+}
+
+// One register in the byte order gdb expects
+fn register(c8: &Chip8, n: usize) -> Option<Vec<u8>> {
+    match n {
+        0...15 => Some(vec![c8.v()[n]]),
+        16 => Some(vec![c8.i() as u8, (c8.i() >> 8) as u8]),
+        17 => Some(vec![c8.pc() as u8, (c8.pc() >> 8) as u8]),
+        18 => Some(vec![c8.sp() as u8]),
+        19 => Some(vec![c8.delay_timer()]),
+        20 => Some(vec![c8.sound_timer]),
+        _ => None,
+    }
+}
+
+impl GdbStub {
+    /// A stub for a newly attached gdb, which expects the target to be stopped.
+    pub fn new() -> GdbStub {
+        let mut debugger = Debugger::new();
+        debugger.take_stop();
+        GdbStub {
+            debugger: debugger,
+            buffer: Vec::new(),
+            breakpoints: HashMap::new(),
+            running: false,
+        }
+    }
+
+    #[inline]
+    pub fn is_paused(&self) -> bool {
+        self.debugger.is_paused()
+    }
+
+    /// Lets the program run on its own again, for when gdb goes away.
+    pub fn detach(&mut self) {
+        self.running = false;
+        self.debugger.resume();
+    }
+
+    /// Handles bytes received from gdb and returns the bytes to send back.
+    pub fn receive(&mut self, c8: &mut Chip8, data: &[u8]) -> Vec<u8> {
+        self.buffer.extend_from_slice(data);
+        let mut out = Vec::new();
+        loop {
+            // acks and anything before a packet start are ignored, ^C interrupts
+            let start = match self.buffer.iter().position(|&b| b == b'$' || b == 0x03) {
+                Some(start) => start,
+                None => {
+                    self.buffer.clear();
+                    break;
+                }
+            };
+            if self.buffer[start] == 0x03 {
+                self.buffer.drain(..start + 1);
+                if self.running {
+                    self.debugger.pause();
+                    out.extend(self.poll_stop());
+                }
+                continue;
+            }
+            let end = match self.buffer[start..].iter().position(|&b| b == b'#') {
+                Some(end) if start + end + 2 < self.buffer.len() => start + end,
+                _ => {
+                    self.buffer.drain(..start);
+                    break;
+                }
+            };
+            let body: Vec<u8> = self.buffer[start + 1..end].to_vec();
+            let sum = String::from_utf8_lossy(&self.buffer[end + 1..end + 3]).into_owned();
+            self.buffer.drain(..end + 3);
+
+            if parse_hex(&sum) != Some(checksum(&body) as usize) {
+                out.push(b'-');
+                continue;
+            }
+            out.push(b'+');
+            let body = String::from_utf8_lossy(&body).into_owned();
+            if let Some(reply) = self.handle(c8, &body) {
+                out.extend(packet(&reply));
+            }
+        }
+        out
+    }
+
+    /// Runs one instruction if gdb has let the program run, returning any stop reply to send.
+    pub fn step(&mut self,
+                c8: &mut Chip8)
+                -> (Result<Option<StepOutcome>, Chip8Error>, Vec<u8>) {
+        let result = self.debugger.step(c8);
+        let reply = match result {
+            Ok(Some(StepOutcome::Exited)) if self.running => {
+                self.running = false;
+                self.debugger.pause();
+                self.debugger.take_stop();
+                packet("W00")
+            }
+            Err(e) if self.running => {
+                self.running = false;
+                self.debugger.take_stop();
+                packet(&format!("S{:02x}",
+                                match e {
+                                    Chip8Error::UnknownOpcode { .. } => SIGILL,
+                                    _ => SIGSEGV,
+                                }))
+            }
+            _ => self.poll_stop(),
+        };
+        (result, reply)
+    }
+
+    // The stop reply once the debugger has paused a continue or step
+    fn poll_stop(&mut self) -> Vec<u8> {
+        match self.debugger.take_stop() {
+            Some(stop) if self.running => {
+                self.running = false;
+                let signal = match stop {
+                    Stop::Paused => SIGINT,
+                    _ => SIGTRAP,
+                };
+                packet(&format!("S{:02x}", signal))
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    // Returns the reply to a packet, or None when the reply comes later as a stop reply
+    fn handle(&mut self, c8: &mut Chip8, body: &str) -> Option<String> {
+        let (command, args) = if body.is_empty() {
+            ("", "")
+        } else {
+            body.split_at(1)
+        };
+        let reply = match command {
+            "?" => format!("S{:02x}", SIGTRAP),
+            "g" => {
+                let mut out = String::new();
+                for n in 0..REGISTER_COUNT {
+                    out.push_str(&hex_bytes(&register(c8, n).unwrap()));
+                }
+                out
+            }
+            "p" => {
+                match parse_hex(args).and_then(|n| register(c8, n)) {
+                    Some(bytes) => hex_bytes(&bytes),
+                    None => String::from("E01"),
+                }
+            }
+            "m" => self.read_memory(c8, args),
+            "c" => {
+                self.running = true;
+                self.debugger.resume();
+                return None;
+            }
+            "s" => {
+                self.running = true;
+                self.debugger.step_into();
+                return None;
+            }
+            "Z" | "z" => self.breakpoint(command == "Z", args),
+            "D" => {
+                self.detach();
+                String::from("OK")
+            }
+            "k" => {
+                self.detach();
+                return None;
+            }
+            "H" => String::from("OK"),
+            "q" => {
+                if args.starts_with("Supported") {
+                    String::from("PacketSize=1000;qXfer:features:read+")
+                } else if args == "Attached" {
+                    String::from("1")
+                } else if args.starts_with("Xfer:features:read:target.xml:") {
+                    self.read_target_xml(&args["Xfer:features:read:target.xml:".len()..])
+                } else if args == "C" {
+                    String::from("QC1")
+                } else if args == "fThreadInfo" {
+                    String::from("m1")
+                } else if args == "sThreadInfo" {
+                    String::from("l")
+                } else {
+                    String::new()
+                }
+            }
+            // anything unsupported gets an empty reply
+            _ => String::new(),
+        };
+        Some(reply)
+    }
+
+    // m<addr>,<len>
+    fn read_memory(&self, c8: &Chip8, args: &str) -> String {
+        let mut parts = args.splitn(2, ',');
+        let addr = parts.next().and_then(parse_hex);
+        let len = parts.next().and_then(parse_hex);
+        match (addr, len) {
+            (Some(addr), Some(len)) if addr < c8.memory().len() => {
+                let end = addr.saturating_add(len).min(c8.memory().len());
+                hex_bytes(&c8.memory()[addr..end])
+            }
+            _ => String::from("E01"),
+        }
+    }
+
+    // Z0,<addr>,<kind> and z0,<addr>,<kind>, only software breakpoints are supported
+    fn breakpoint(&mut self, insert: bool, args: &str) -> String {
+        let mut parts = args.split(',');
+        if parts.next() != Some("0") {
+            return String::new();
+        }
+        let addr = match parts.next().and_then(parse_hex) {
+            Some(addr) if addr <= 0xFFFF => addr as u16,
+            _ => return String::from("E01"),
+        };
+        if insert {
+            if !self.breakpoints.contains_key(&addr) {
+                let id = self.debugger.add_breakpoint(Breakpoint::Address(addr));
+                self.breakpoints.insert(addr, id);
+            }
+        } else if let Some(id) = self.breakpoints.remove(&addr) {
+            self.debugger.delete(id);
+        }
+        String::from("OK")
+    }
+
+    // <offset>,<length>, replies with l when this is the last chunk and m otherwise
+    fn read_target_xml(&self, args: &str) -> String {
+        let xml = target_xml();
+        let mut parts = args.splitn(2, ',');
+        let offset = parts.next().and_then(parse_hex).unwrap_or(0).min(xml.len());
+        let length = parts.next().and_then(parse_hex).unwrap_or(xml.len());
+        let end = offset.saturating_add(length).min(xml.len());
+        format!("{}{}",
+                if end == xml.len() {
+                    "l"
+                } else {
+                    "m"
+                },
+                &xml[offset..end])
+    }
+}
@@ -0,0 +1,88 @@
+use super::*;
+use asm::assemble;
+
+fn machine() -> Chip8 {
+    let mut c8 = Chip8::init();
+    c8.load_rom(&assemble("v0 := 0x12\n\
+                           i := 0x234\n\
+                           v1 := 1\n\
+                           : loop\n\
+                           jump loop")
+                    .unwrap());
+    c8
+}
+
+// Sends one packet like a gdb client would, returning the stub's reply
+fn send(stub: &mut GdbStub, c8: &mut Chip8, body: &str) -> String {
+    String::from_utf8(stub.receive(c8, &packet(body))).unwrap()
+}
+
+// Steps until the stub has a stop reply for us
+fn run(stub: &mut GdbStub, c8: &mut Chip8) -> String {
+    for _ in 0..100 {
+        let (_, reply) = stub.step(c8);
+        if !reply.is_empty() {
+            return String::from_utf8(reply).unwrap();
+        }
+    }
+    String::new()
+}
+
+#[test]
+fn framing() {
+    let mut c8 = machine();
+    let mut stub = GdbStub::new();
+    assert_eq!(packet("OK"), b"$OK#9a".to_vec());
+    // bad checksums are nacked, split packets wait for the rest
+    assert_eq!(stub.receive(&mut c8, b"+$?#00"), b"-".to_vec());
+    assert_eq!(stub.receive(&mut c8, b"$?#"), Vec::<u8>::new());
+    assert_eq!(stub.receive(&mut c8, b"3f"), b"+$S05#b8".to_vec());
+}
+
+#[test]
+fn registers_and_memory() {
+    let mut c8 = machine();
+    let mut stub = GdbStub::new();
+    send(&mut stub, &mut c8, "s");
+    run(&mut stub, &mut c8);
+    send(&mut stub, &mut c8, "s");
+    run(&mut stub, &mut c8);
+
+    let regs = send(&mut stub, &mut c8, "g");
+    assert_eq!(&regs[..5], "+$120");
+    // v0-vf, then i, pc, sp, dt and st
+    assert_eq!(&regs[34..48], "34020402100000");
+    assert_eq!(send(&mut stub, &mut c8, "p11"), "+$0402#c6");
+    assert_eq!(send(&mut stub, &mut c8, "p15"), "+$E01#a6");
+    assert_eq!(send(&mut stub, &mut c8, "m200,4"), "+$6012a234#c3");
+    assert_eq!(send(&mut stub, &mut c8, "m10000,4"), "+$E01#a6");
+}
+
+#[test]
+fn breakpoints_and_continue() {
+    let mut c8 = machine();
+    let mut stub = GdbStub::new();
+    assert_eq!(send(&mut stub, &mut c8, "Z0,204,2"), "+$OK#9a");
+    assert_eq!(send(&mut stub, &mut c8, "c"), "+");
+    assert_eq!(run(&mut stub, &mut c8), "$S05#b8");
+    assert_eq!(c8.pc(), 0x204);
+
+    // with the breakpoint gone it runs until interrupted
+    assert_eq!(send(&mut stub, &mut c8, "z0,204,2"), "+$OK#9a");
+    send(&mut stub, &mut c8, "c");
+    assert_eq!(run(&mut stub, &mut c8), "");
+    assert!(!stub.is_paused());
+    assert_eq!(stub.receive(&mut c8, &[0x03]), b"$S02#b5".to_vec());
+    assert!(stub.is_paused());
+}
+
+#[test]
+fn target_description() {
+    let mut c8 = machine();
+    let mut stub = GdbStub::new();
+    let reply = send(&mut stub, &mut c8, "qXfer:features:read:target.xml:0,10");
+    assert_eq!(&reply[..19], "+$m<?xml version=\"1");
+    let reply = send(&mut stub, &mut c8, "qXfer:features:read:target.xml:0,1000");
+    assert!(reply.starts_with("+$l<?xml"));
+    assert!(reply.contains("<reg name=\"pc\" bitsize=\"16\" type=\"code_ptr\"/>"));
+}
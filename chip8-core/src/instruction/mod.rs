@@ -0,0 +1,370 @@
+// The CHIP-8, SCHIP and XO-CHIP instruction set as data.
+//
+// Register operands are register indices 0x0-0xF, addresses are 12 bit unless noted.
+
+use std::fmt;
+
+use chip8::ByteManip;
+
+#[cfg(test)]
+mod tests;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Instruction {
+    // 0x00CN scroll the display down N lines (SCHIP)
+    ScrollDown(u8),
+    // 0x00DN scroll the display up N lines (XO-CHIP)
+    ScrollUp(u8),
+    // 0x00E0 clear the screen
+    Clear,
+    // 0x00EE return from subroutine
+    Return,
+    // 0x00FB scroll right 4 pixels (SCHIP)
+    ScrollRight,
+    // 0x00FC scroll left 4 pixels (SCHIP)
+    ScrollLeft,
+    // 0x00FD exit the interpreter (SCHIP)
+    Exit,
+    // 0x00FE low resolution mode (SCHIP)
+    Lores,
+    // 0x00FF high resolution mode (SCHIP)
+    Hires,
+    // 0x1NNN jump to NNN
+    Jump(u16),
+    // 0x2NNN call subroutine at NNN
+    Call(u16),
+    // 0x3XNN skip if VX == NN
+    SkipEqImm(u8, u8),
+    // 0x4XNN skip if VX != NN
+    SkipNeImm(u8, u8),
+    // 0x5XY0 skip if VX == VY
+    SkipEqReg(u8, u8),
+    // 0x5XY2 store VX to VY at I (XO-CHIP)
+    SaveRange(u8, u8),
+    // 0x5XY3 load VX to VY from I (XO-CHIP)
+    LoadRange(u8, u8),
+    // 0x6XNN VX = NN
+    LoadImm(u8, u8),
+    // 0x7XNN VX += NN
+    AddImm(u8, u8),
+    // 0x8XY0 VX = VY
+    Move(u8, u8),
+    // 0x8XY1 VX |= VY
+    Or(u8, u8),
+    // 0x8XY2 VX &= VY
+    And(u8, u8),
+    // 0x8XY3 VX ^= VY
+    Xor(u8, u8),
+    // 0x8XY4 VX += VY, VF = carry
+    Add(u8, u8),
+    // 0x8XY5 VX -= VY, VF = no borrow
+    Sub(u8, u8),
+    // 0x8XY6 VX >>= 1, VF = shifted out bit
+    ShiftRight(u8, u8),
+    // 0x8XY7 VX = VY - VX, VF = no borrow
+    SubReverse(u8, u8),
+    // 0x8XYE VX <<= 1, VF = shifted out bit
+    ShiftLeft(u8, u8),
+    // 0x9XY0 skip if VX != VY
+    SkipNeReg(u8, u8),
+    // 0xANNN I = NNN
+    LoadI(u16),
+    // 0xBNNN jump to NNN + V0
+    JumpOffset(u16),
+    // 0xCXNN VX = random & NN
+    Random(u8, u8),
+    // 0xDXYN draw an N row sprite at VX, VY
+    Draw(u8, u8, u8),
+    // 0xEX9E skip if key VX is pressed
+    SkipKey(u8),
+    // 0xEXA1 skip if key VX is not pressed
+    SkipNotKey(u8),
+    // 0xF000 NNNN I = NNNN, the address is the following word (XO-CHIP)
+    LoadLongI,
+    // 0xFN01 select drawing planes N (XO-CHIP)
+    Plane(u8),
+    // 0xF002 load the audio pattern at I (XO-CHIP)
+    Audio,
+    // 0xFX07 VX = delay timer
+    GetDelay(u8),
+    // 0xFX0A wait for a key press, store it in VX
+    WaitKey(u8),
+    // 0xFX15 delay timer = VX
+    SetDelay(u8),
+    // 0xFX18 sound timer = VX
+    SetSound(u8),
+    // 0xFX1E I += VX
+    AddI(u8),
+    // 0xFX29 I = small font character VX
+    Font(u8),
+    // 0xFX30 I = big font character VX (SCHIP)
+    BigFont(u8),
+    // 0xFX33 store BCD of VX at I
+    Bcd(u8),
+    // 0xFX3A audio pitch = VX (XO-CHIP)
+    Pitch(u8),
+    // 0xFX55 store V0 to VX at I
+    Save(u8),
+    // 0xFX65 load V0 to VX from I
+    Load(u8),
+    // 0xFX75 store V0 to VX in user flags (SCHIP)
+    SaveFlags(u8),
+    // 0xFX85 load V0 to VX from user flags (SCHIP)
+    LoadFlags(u8),
+}
+
+impl Instruction {
+    /// Decodes an opcode. Every opcode that decodes re-encodes to exactly the same value, so
+    /// opcodes with stray bits set in unused nibbles are rejected.
+    pub fn decode(op: u16) -> Option<Instruction> {
+        use self::Instruction::*;
+
+        let x = op.x() as u8;
+        let y = op.y() as u8;
+        let n = op.nibble(4);
+        let nn = op.low_byte();
+        let nnn = op.nnn();
+        Some(match op & 0xF000 {
+            0x0000 => {
+                match op {
+                    0x00C0...0x00CF => ScrollDown(n),
+                    0x00D0...0x00DF => ScrollUp(n),
+                    0x00E0 => Clear,
+                    0x00EE => Return,
+                    0x00FB => ScrollRight,
+                    0x00FC => ScrollLeft,
+                    0x00FD => Exit,
+                    0x00FE => Lores,
+                    0x00FF => Hires,
+                    _ => return None,
+                }
+            }
+            0x1000 => Jump(nnn),
+            0x2000 => Call(nnn),
+            0x3000 => SkipEqImm(x, nn),
+            0x4000 => SkipNeImm(x, nn),
+            0x5000 => {
+                match n {
+                    0x0 => SkipEqReg(x, y),
+                    0x2 => SaveRange(x, y),
+                    0x3 => LoadRange(x, y),
+                    _ => return None,
+                }
+            }
+            0x6000 => LoadImm(x, nn),
+            0x7000 => AddImm(x, nn),
+            0x8000 => {
+                match n {
+                    0x0 => Move(x, y),
+                    0x1 => Or(x, y),
+                    0x2 => And(x, y),
+                    0x3 => Xor(x, y),
+                    0x4 => Add(x, y),
+                    0x5 => Sub(x, y),
+                    0x6 => ShiftRight(x, y),
+                    0x7 => SubReverse(x, y),
+                    0xE => ShiftLeft(x, y),
+                    _ => return None,
+                }
+            }
+            0x9000 if n == 0 => SkipNeReg(x, y),
+            0xA000 => LoadI(nnn),
+            0xB000 => JumpOffset(nnn),
+            0xC000 => Random(x, nn),
+            0xD000 => Draw(x, y, n),
+            0xE000 => {
+                match nn {
+                    0x9E => SkipKey(x),
+                    0xA1 => SkipNotKey(x),
+                    _ => return None,
+                }
+            }
+            0xF000 => {
+                match nn {
+                    0x00 if x == 0 => LoadLongI,
+                    0x01 => Plane(x),
+                    0x02 if x == 0 => Audio,
+                    0x07 => GetDelay(x),
+                    0x0A => WaitKey(x),
+                    0x15 => SetDelay(x),
+                    0x18 => SetSound(x),
+                    0x1E => AddI(x),
+                    0x29 => Font(x),
+                    0x30 => BigFont(x),
+                    0x33 => Bcd(x),
+                    0x3A => Pitch(x),
+                    0x55 => Save(x),
+                    0x65 => Load(x),
+                    0x75 => SaveFlags(x),
+                    0x85 => LoadFlags(x),
+                    _ => return None,
+                }
+            }
+            _ => return None,
+        })
+    }
+
+    /// Encodes back to the opcode. For `LoadLongI` this is only the first word, the address
+    /// follows it.
+    pub fn encode(&self) -> u16 {
+        use self::Instruction::*;
+
+        fn xy(base: u16, x: u8, y: u8) -> u16 {
+            base | (x as u16 & 0xF) << 8 | (y as u16 & 0xF) << 4
+        }
+        fn xnn(base: u16, x: u8, nn: u8) -> u16 {
+            base | (x as u16 & 0xF) << 8 | nn as u16
+        }
+
+        match *self {
+            ScrollDown(n) => 0x00C0 | (n as u16 & 0xF),
+            ScrollUp(n) => 0x00D0 | (n as u16 & 0xF),
+            Clear => 0x00E0,
+            Return => 0x00EE,
+            ScrollRight => 0x00FB,
+            ScrollLeft => 0x00FC,
+            Exit => 0x00FD,
+            Lores => 0x00FE,
+            Hires => 0x00FF,
+            Jump(nnn) => 0x1000 | (nnn & 0x0FFF),
+            Call(nnn) => 0x2000 | (nnn & 0x0FFF),
+            SkipEqImm(x, nn) => xnn(0x3000, x, nn),
+            SkipNeImm(x, nn) => xnn(0x4000, x, nn),
+            SkipEqReg(x, y) => xy(0x5000, x, y),
+            SaveRange(x, y) => xy(0x5002, x, y),
+            LoadRange(x, y) => xy(0x5003, x, y),
+            LoadImm(x, nn) => xnn(0x6000, x, nn),
+            AddImm(x, nn) => xnn(0x7000, x, nn),
+            Move(x, y) => xy(0x8000, x, y),
+            Or(x, y) => xy(0x8001, x, y),
+            And(x, y) => xy(0x8002, x, y),
+            Xor(x, y) => xy(0x8003, x, y),
+            Add(x, y) => xy(0x8004, x, y),
+            Sub(x, y) => xy(0x8005, x, y),
+            ShiftRight(x, y) => xy(0x8006, x, y),
+            SubReverse(x, y) => xy(0x8007, x, y),
+            ShiftLeft(x, y) => xy(0x800E, x, y),
+            SkipNeReg(x, y) => xy(0x9000, x, y),
+            LoadI(nnn) => 0xA000 | (nnn & 0x0FFF),
+            JumpOffset(nnn) => 0xB000 | (nnn & 0x0FFF),
+            Random(x, nn) => xnn(0xC000, x, nn),
+            Draw(x, y, n) => xy(0xD000, x, y) | (n as u16 & 0xF),
+            SkipKey(x) => xnn(0xE000, x, 0x9E),
+            SkipNotKey(x) => xnn(0xE000, x, 0xA1),
+            LoadLongI => 0xF000,
+            Plane(n) => xnn(0xF000, n, 0x01),
+            Audio => 0xF002,
+            GetDelay(x) => xnn(0xF000, x, 0x07),
+            WaitKey(x) => xnn(0xF000, x, 0x0A),
+            SetDelay(x) => xnn(0xF000, x, 0x15),
+            SetSound(x) => xnn(0xF000, x, 0x18),
+            AddI(x) => xnn(0xF000, x, 0x1E),
+            Font(x) => xnn(0xF000, x, 0x29),
+            BigFont(x) => xnn(0xF000, x, 0x30),
+            Bcd(x) => xnn(0xF000, x, 0x33),
+            Pitch(x) => xnn(0xF000, x, 0x3A),
+            Save(x) => xnn(0xF000, x, 0x55),
+            Load(x) => xnn(0xF000, x, 0x65),
+            SaveFlags(x) => xnn(0xF000, x, 0x75),
+            LoadFlags(x) => xnn(0xF000, x, 0x85),
+        }
+    }
+
+    /// Length in bytes, 4 for the two word XO-CHIP `0xF000 NNNN` and 2 for everything else.
+    pub fn len(&self) -> u16 {
+        match *self {
+            Instruction::LoadLongI => 4,
+            _ => 2,
+        }
+    }
+
+    /// True for the instructions only XO-CHIP interpreters understand.
+    pub fn is_xo_chip(&self) -> bool {
+        match *self {
+            Instruction::ScrollUp(_) |
+            Instruction::SaveRange(..) |
+            Instruction::LoadRange(..) |
+            Instruction::LoadLongI |
+            Instruction::Plane(_) |
+            Instruction::Audio |
+            Instruction::Pitch(_) => true,
+            _ => false,
+        }
+    }
+
+    /// True for the conditional skips, whose next instruction may or may not run.
+    pub fn is_skip(&self) -> bool {
+        match *self {
+            Instruction::SkipEqImm(..) |
+            Instruction::SkipNeImm(..) |
+            Instruction::SkipEqReg(..) |
+            Instruction::SkipNeReg(..) |
+            Instruction::SkipKey(_) |
+            Instruction::SkipNotKey(_) => true,
+            _ => false,
+        }
+    }
+}
+
+/// Octo assembly syntax, with addresses as hex numbers. `LoadLongI` is printed without its
+/// address, which lives in the next word.
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use self::Instruction::*;
+
+        match *self {
+            ScrollDown(n) => write!(f, "scroll-down {}", n),
+            ScrollUp(n) => write!(f, "scroll-up {}", n),
+            Clear => write!(f, "clear"),
+            Return => write!(f, "return"),
+            ScrollRight => write!(f, "scroll-right"),
+            ScrollLeft => write!(f, "scroll-left"),
+            Exit => write!(f, "exit"),
+            Lores => write!(f, "lores"),
+            Hires => write!(f, "hires"),
+            Jump(nnn) => write!(f, "jump 0x{:03X}", nnn),
+            Call(nnn) => write!(f, ":call 0x{:03X}", nnn),
+            // Octo's `if ... then` runs the next line when the condition holds, so each skip is
+            // written as the opposite condition
+            SkipEqImm(x, nn) => write!(f, "if v{:x} != 0x{:02X} then", x, nn),
+            SkipNeImm(x, nn) => write!(f, "if v{:x} == 0x{:02X} then", x, nn),
+            SkipEqReg(x, y) => write!(f, "if v{:x} != v{:x} then", x, y),
+            SaveRange(x, y) => write!(f, "save v{:x} - v{:x}", x, y),
+            LoadRange(x, y) => write!(f, "load v{:x} - v{:x}", x, y),
+            LoadImm(x, nn) => write!(f, "v{:x} := 0x{:02X}", x, nn),
+            AddImm(x, nn) => write!(f, "v{:x} += 0x{:02X}", x, nn),
+            Move(x, y) => write!(f, "v{:x} := v{:x}", x, y),
+            Or(x, y) => write!(f, "v{:x} |= v{:x}", x, y),
+            And(x, y) => write!(f, "v{:x} &= v{:x}", x, y),
+            Xor(x, y) => write!(f, "v{:x} ^= v{:x}", x, y),
+            Add(x, y) => write!(f, "v{:x} += v{:x}", x, y),
+            Sub(x, y) => write!(f, "v{:x} -= v{:x}", x, y),
+            ShiftRight(x, y) => write!(f, "v{:x} >>= v{:x}", x, y),
+            SubReverse(x, y) => write!(f, "v{:x} =- v{:x}", x, y),
+            ShiftLeft(x, y) => write!(f, "v{:x} <<= v{:x}", x, y),
+            SkipNeReg(x, y) => write!(f, "if v{:x} == v{:x} then", x, y),
+            LoadI(nnn) => write!(f, "i := 0x{:03X}", nnn),
+            JumpOffset(nnn) => write!(f, "jump0 0x{:03X}", nnn),
+            Random(x, nn) => write!(f, "v{:x} := random 0x{:02X}", x, nn),
+            Draw(x, y, n) => write!(f, "sprite v{:x} v{:x} {}", x, y, n),
+            SkipKey(x) => write!(f, "if v{:x} -key then", x),
+            SkipNotKey(x) => write!(f, "if v{:x} key then", x),
+            LoadLongI => write!(f, "i := long"),
+            Plane(n) => write!(f, "plane {}", n),
+            Audio => write!(f, "audio"),
+            GetDelay(x) => write!(f, "v{:x} := delay", x),
+            WaitKey(x) => write!(f, "v{:x} := key", x),
+            SetDelay(x) => write!(f, "delay := v{:x}", x),
+            SetSound(x) => write!(f, "buzzer := v{:x}", x),
+            AddI(x) => write!(f, "i += v{:x}", x),
+            Font(x) => write!(f, "i := hex v{:x}", x),
+            BigFont(x) => write!(f, "i := bighex v{:x}", x),
+            Bcd(x) => write!(f, "bcd v{:x}", x),
+            Pitch(x) => write!(f, "pitch := v{:x}", x),
+            Save(x) => write!(f, "save v{:x}", x),
+            Load(x) => write!(f, "load v{:x}", x),
+            SaveFlags(x) => write!(f, "saveflags v{:x}", x),
+            LoadFlags(x) => write!(f, "loadflags v{:x}", x),
+        }
+    }
+}
@@ -0,0 +1,42 @@
+use super::*;
+use super::Instruction::*;
+
+#[test]
+fn decode_operands() {
+    assert_eq!(Instruction::decode(0x00E0), Some(Clear));
+    assert_eq!(Instruction::decode(0x00C3), Some(ScrollDown(3)));
+    assert_eq!(Instruction::decode(0x1234), Some(Jump(0x234)));
+    assert_eq!(Instruction::decode(0x3A42), Some(SkipEqImm(0xA, 0x42)));
+    assert_eq!(Instruction::decode(0x5AB3), Some(LoadRange(0xA, 0xB)));
+    assert_eq!(Instruction::decode(0x8AB6), Some(ShiftRight(0xA, 0xB)));
+    assert_eq!(Instruction::decode(0xD12F), Some(Draw(1, 2, 0xF)));
+    assert_eq!(Instruction::decode(0xF000), Some(LoadLongI));
+    assert_eq!(Instruction::decode(0xF301), Some(Plane(3)));
+    assert_eq!(Instruction::decode(0xF533), Some(Bcd(5)));
+}
+
+#[test]
+fn decode_rejects_unknown() {
+    for &op in &[0x0000, 0x00EF, 0x5121, 0x8008, 0x9001, 0xE000, 0xF100, 0xF102, 0xFFFF] {
+        assert_eq!(Instruction::decode(op), None, "0x{:04X}", op);
+    }
+}
+
+#[test]
+fn encode_roundtrip() {
+    for op in 0..0x10000 {
+        let op = op as u16;
+        if let Some(i) = Instruction::decode(op) {
+            assert_eq!(i.encode(), op, "{:?}", i);
+        }
+    }
+}
+
+#[test]
+fn lengths() {
+    assert_eq!(LoadLongI.len(), 4);
+    assert_eq!(Jump(0x200).len(), 2);
+    assert!(SkipKey(0).is_skip());
+    assert!(Plane(1).is_xo_chip());
+    assert!(!Draw(0, 0, 0).is_xo_chip());
+}
@@ -0,0 +1,22 @@
+//! CHIP-8, SCHIP and XO-CHIP emulator core.
+//!
+//! Holds the machine and everything needed to drive it, without any graphics, audio or input
+//! dependencies, so it builds on stable Rust and can be embedded in other tools. Frontends feed
+//! it key presses through `Chip8::update_keys`, call `Chip8::step` and `Chip8::tick`, and draw
+//! `Chip8::gfx`.
+
+mod chip8;
+mod instruction;
+pub mod disasm;
+pub mod asm;
+pub mod debugger;
+pub mod gdb;
+pub mod trace;
+pub mod movie;
+pub mod vip;
+
+pub use chip8::{Chip8, Chip8Error, StepOutcome, ByteManip};
+pub use instruction::Instruction;
+pub use chip8::{Quirks, MemoryIncrement, VIP_MAX_IPF};
+pub use chip8::{StateError, rom_hash};
+pub use chip8::{Rng, XorShift};
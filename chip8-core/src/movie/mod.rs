@@ -0,0 +1,395 @@
+// Input movies: every keypad change of a run, with what's needed to repeat it exactly.
+//
+// Playback is only deterministic when the machine runs a fixed number of steps per 60Hz frame,
+// so a movie records that rate along with the ROM hash, seed and quirks. Key changes are keyed
+// by frame and by instruction count, and apply before the instruction with that count.
+//
+// The file is plain text, one record per line:
+//
+//   chip8-movie 1
+//   rom 0123456789ABCDEF
+//   seed 42
+//   steps-per-frame 10
+//   quirks shift_uses_vy=0 memory_increment=unchanged jump_uses_vx=1 ...
+//   key <frame> <cycle> <key> down|up
+//   end <frames> <cycles> <framebuffer hash>
+
+use std::error;
+use std::fmt;
+
+use chip8::{Chip8, MemoryIncrement, Quirks, rom_hash};
+
+#[cfg(test)]
+mod tests;
+
+const HEADER: &'static str = "chip8-movie 1";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyEvent {
+    pub frame: u64,
+    // instructions executed before the change
+    pub cycle: u64,
+    pub key: u8,
+    pub pressed: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Movie {
+    pub rom_hash: u64,
+    pub seed: u64,
+    pub steps_per_frame: u32,
+    pub quirks: Quirks,
+    pub events: Vec<KeyEvent>,
+    // length of the recording and the screen it ended on
+    pub frames: u64,
+    pub cycles: u64,
+    pub framebuffer_hash: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MovieError {
+    Parse { line: usize, message: String },
+    // the movie was recorded with a different ROM
+    RomMismatch { expected: u64, found: u64 },
+    // playback ended on a different screen than the recording
+    Desync { expected: u64, found: u64 },
+}
+
+impl fmt::Display for MovieError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            MovieError::Parse { line, ref message } => write!(f, "line {}: {}", line, message),
+            MovieError::RomMismatch { expected, found } => {
+                write!(f,
+                       "movie is for ROM {:016X}, but ROM {:016X} is loaded",
+                       found,
+                       expected)
+            }
+            MovieError::Desync { expected, found } => {
+                write!(f,
+                       "playback desynced, framebuffer hash is {:016X} instead of {:016X}",
+                       found,
+                       expected)
+            }
+        }
+    }
+}
+
+impl error::Error for MovieError {
+    fn description(&self) -> &str {
+        match *self {
+            MovieError::Parse { .. } => "invalid movie file",
+            MovieError::RomMismatch { .. } => "movie is for a different ROM",
+            MovieError::Desync { .. } => "playback desynced",
+        }
+    }
+}
+
+/// Hash of everything on screen, compared at the end of playback.
+pub fn framebuffer_hash(c8: &Chip8) -> u64 {
+    rom_hash(&c8.gfx)
+}
+
+fn memory_increment_name(increment: MemoryIncrement) -> &'static str {
+    match increment {
+        MemoryIncrement::XPlusOne => "x+1",
+        MemoryIncrement::X => "x",
+        MemoryIncrement::Unchanged => "unchanged",
+    }
+}
+
+fn parse_quirks(words: &[&str]) -> Result<Quirks, String> {
+    let mut quirks = Quirks::default();
+    for word in words {
+        let mut parts = word.splitn(2, '=');
+        let name = parts.next().unwrap();
+        let value = try!(parts.next().ok_or(format!("quirk {} has no value", name)));
+        let flag = value == "1";
+        match name {
+            "shift_uses_vy" => quirks.shift_uses_vy = flag,
+            "jump_uses_vx" => quirks.jump_uses_vx = flag,
+            "clip_sprites" => quirks.clip_sprites = flag,
+            "logic_resets_vf" => quirks.logic_resets_vf = flag,
+            "xo_chip" => quirks.xo_chip = flag,
+            "display_wait" => quirks.display_wait = flag,
+            "vip_timing" => quirks.vip_timing = flag,
+            "memory_increment" => {
+                quirks.memory_increment = match value {
+                    "x+1" => MemoryIncrement::XPlusOne,
+                    "x" => MemoryIncrement::X,
+                    "unchanged" => MemoryIncrement::Unchanged,
+                    _ => return Err(format!("unknown memory increment {}", value)),
+                }
+            }
+            _ => return Err(format!("unknown quirk {}", name)),
+        }
+    }
+    Ok(quirks)
+}
+
+fn parse_number<T: ::std::str::FromStr>(text: Option<&&str>) -> Result<T, String> {
+    match text {
+        Some(text) => text.parse().map_err(|_| format!("invalid number {}", text)),
+        None => Err(String::from("missing number")),
+    }
+}
+
+fn parse_hex(text: Option<&&str>) -> Result<u64, String> {
+    match text {
+        Some(text) => u64::from_str_radix(text, 16).map_err(|_| format!("invalid hash {}", text)),
+        None => Err(String::from("missing hash")),
+    }
+}
+
+// key <frame> <cycle> <key> down|up
+fn parse_key(words: &[&str]) -> Result<KeyEvent, String> {
+    let frame = try!(parse_number(words.get(1)));
+    let cycle = try!(parse_number(words.get(2)));
+    let key = match words.get(3).and_then(|k| u8::from_str_radix(k, 16).ok()) {
+        Some(key) if key < 16 => key,
+        _ => return Err(String::from("invalid key")),
+    };
+    let pressed = match words.get(4) {
+        Some(&"down") => true,
+        Some(&"up") => false,
+        _ => return Err(String::from("expected down or up")),
+    };
+    Ok(KeyEvent {
+        frame: frame,
+        cycle: cycle,
+        key: key,
+        pressed: pressed,
+    })
+}
+
+// end <frames> <cycles> <framebuffer hash>
+fn parse_end(words: &[&str]) -> Result<(u64, u64, u64), String> {
+    let frames = try!(parse_number(words.get(1)));
+    let cycles = try!(parse_number(words.get(2)));
+    let hash = try!(parse_hex(words.get(3)));
+    Ok((frames, cycles, hash))
+}
+
+impl Movie {
+    pub fn to_text(&self) -> String {
+        let q = &self.quirks;
+        let mut out = format!("{}\nrom {:016X}\nseed {}\nsteps-per-frame {}\n",
+                              HEADER,
+                              self.rom_hash,
+                              self.seed,
+                              self.steps_per_frame);
+        out.push_str(&format!("quirks shift_uses_vy={} memory_increment={} jump_uses_vx={} \
+                               clip_sprites={} logic_resets_vf={} xo_chip={} display_wait={} \
+                               vip_timing={}\n",
+                              q.shift_uses_vy as u8,
+                              memory_increment_name(q.memory_increment),
+                              q.jump_uses_vx as u8,
+                              q.clip_sprites as u8,
+                              q.logic_resets_vf as u8,
+                              q.xo_chip as u8,
+                              q.display_wait as u8,
+                              q.vip_timing as u8));
+        for e in &self.events {
+            out.push_str(&format!("key {} {} {:X} {}\n",
+                                  e.frame,
+                                  e.cycle,
+                                  e.key,
+                                  if e.pressed {
+                                      "down"
+                                  } else {
+                                      "up"
+                                  }));
+        }
+        out.push_str(&format!("end {} {} {:016X}\n",
+                              self.frames,
+                              self.cycles,
+                              self.framebuffer_hash));
+        out
+    }
+
+    pub fn parse(text: &str) -> Result<Movie, MovieError> {
+        let mut movie = Movie {
+            rom_hash: 0,
+            seed: 0,
+            steps_per_frame: 0,
+            quirks: Quirks::default(),
+            events: Vec::new(),
+            frames: 0,
+            cycles: 0,
+            framebuffer_hash: 0,
+        };
+        let mut lines = text.lines().enumerate();
+        match lines.next() {
+            Some((_, line)) if line.trim() == HEADER => {}
+            _ => {
+                return Err(MovieError::Parse {
+                    line: 1,
+                    message: String::from("not a movie file"),
+                })
+            }
+        }
+        let mut ended = false;
+        for (n, line) in lines {
+            let words: Vec<&str> = line.split_whitespace().collect();
+            if words.is_empty() {
+                continue;
+            }
+            let parsed = match words[0] {
+                "rom" => parse_hex(words.get(1)).map(|h| movie.rom_hash = h),
+                "seed" => parse_number(words.get(1)).map(|s| movie.seed = s),
+                "steps-per-frame" => parse_number(words.get(1)).map(|s| movie.steps_per_frame = s),
+                "quirks" => parse_quirks(&words[1..]).map(|q| movie.quirks = q),
+                "key" => parse_key(&words).map(|e| movie.events.push(e)),
+                "end" => {
+                    ended = true;
+                    parse_end(&words).map(|(frames, cycles, hash)| {
+                        movie.frames = frames;
+                        movie.cycles = cycles;
+                        movie.framebuffer_hash = hash;
+                    })
+                }
+                other => Err(format!("unknown record {}", other)),
+            };
+            if let Err(message) = parsed {
+                return Err(MovieError::Parse {
+                    line: n + 1,
+                    message: message,
+                });
+            }
+        }
+        if !ended || movie.steps_per_frame == 0 {
+            return Err(MovieError::Parse {
+                line: text.lines().count(),
+                message: String::from("movie is incomplete"),
+            });
+        }
+        Ok(movie)
+    }
+}
+
+/// Records key changes while the frontend runs the machine frame-locked.
+pub struct Recorder {
+    movie: Movie,
+    frame: u64,
+    cycle: u64,
+}
+
+impl Recorder {
+    /// Starts recording from the machine as it is now, seeding it with `seed`. Start right after
+    /// loading the ROM.
+    pub fn start(c8: &mut Chip8, seed: u64, steps_per_frame: u32) -> Recorder {
+        c8.seed(seed);
+        Recorder {
+            movie: Movie {
+                rom_hash: c8.rom_hash(),
+                seed: seed,
+                steps_per_frame: steps_per_frame,
+                quirks: c8.quirks,
+                events: Vec::new(),
+                frames: 0,
+                cycles: 0,
+                framebuffer_hash: 0,
+            },
+            frame: 0,
+            cycle: 0,
+        }
+    }
+
+    /// Passes a key change on to the machine and records it.
+    pub fn key(&mut self, c8: &mut Chip8, key: u8, pressed: bool) {
+        c8.update_keys(key, pressed);
+        self.movie.events.push(KeyEvent {
+            frame: self.frame,
+            cycle: self.cycle,
+            key: key,
+            pressed: pressed,
+        });
+    }
+
+    /// Counts a call to `Chip8::step`.
+    pub fn stepped(&mut self) {
+        self.cycle += 1;
+    }
+
+    /// Counts a call to `Chip8::tick`.
+    pub fn ticked(&mut self) {
+        self.frame += 1;
+    }
+
+    pub fn finish(mut self, c8: &Chip8) -> Movie {
+        self.movie.frames = self.frame;
+        self.movie.cycles = self.cycle;
+        self.movie.framebuffer_hash = framebuffer_hash(c8);
+        self.movie
+    }
+}
+
+/// Feeds a movie's key changes back into the machine.
+pub struct Player {
+    movie: Movie,
+    next: usize,
+    frame: u64,
+    cycle: u64,
+}
+
+impl Player {
+    /// Sets the machine up like the recording, which must be for the loaded ROM.
+    pub fn start(movie: Movie, c8: &mut Chip8) -> Result<Player, MovieError> {
+        if movie.rom_hash != c8.rom_hash() {
+            return Err(MovieError::RomMismatch {
+                expected: c8.rom_hash(),
+                found: movie.rom_hash,
+            });
+        }
+        c8.seed(movie.seed);
+        c8.quirks = movie.quirks;
+        Ok(Player {
+            movie: movie,
+            next: 0,
+            frame: 0,
+            cycle: 0,
+        })
+    }
+
+    #[inline]
+    pub fn steps_per_frame(&self) -> u32 {
+        self.movie.steps_per_frame
+    }
+
+    /// Applies the key changes due before the next call to `Chip8::step`.
+    pub fn apply(&mut self, c8: &mut Chip8) {
+        while let Some(e) = self.movie.events.get(self.next) {
+            if e.cycle > self.cycle {
+                break;
+            }
+            c8.update_keys(e.key, e.pressed);
+            self.next += 1;
+        }
+    }
+
+    pub fn stepped(&mut self) {
+        self.cycle += 1;
+    }
+
+    pub fn ticked(&mut self) {
+        self.frame += 1;
+    }
+
+    /// True once as many frames have run as were recorded.
+    pub fn done(&self) -> bool {
+        self.frame >= self.movie.frames
+    }
+
+    /// Checks the screen matches the one the recording ended on.
+    pub fn verify(&self, c8: &Chip8) -> Result<(), MovieError> {
+        let found = framebuffer_hash(c8);
+        if found == self.movie.framebuffer_hash {
+            Ok(())
+        } else {
+            Err(MovieError::Desync {
+                expected: self.movie.framebuffer_hash,
+                found: found,
+            })
+        }
+    }
+}
@@ -0,0 +1,100 @@
+use super::*;
+use asm::assemble;
+use chip8::StepOutcome;
+
+// Waits for a key, then draws a random sprite at the key's position
+const PROGRAM: &'static str = "\
+: loop\n\
+    v0 := key\n\
+    v1 := random 0xFF\n\
+    i := hex v1\n\
+    sprite v0 v0 5\n\
+    jump loop";
+
+fn machine() -> Chip8 {
+    let mut c8 = Chip8::init();
+    c8.load_rom(&assemble(PROGRAM).unwrap());
+    c8
+}
+
+fn record() -> Movie {
+    let mut c8 = machine();
+    let mut recorder = Recorder::start(&mut c8, 1234, 10);
+    for frame in 0..20 {
+        if frame % 4 == 1 {
+            recorder.key(&mut c8, (frame / 4) as u8, true);
+        } else if frame % 4 == 2 {
+            recorder.key(&mut c8, (frame / 4) as u8, false);
+        }
+        for _ in 0..10 {
+            c8.step().unwrap();
+            recorder.stepped();
+        }
+        c8.tick();
+        recorder.ticked();
+    }
+    recorder.finish(&c8)
+}
+
+fn play(movie: Movie, c8: &mut Chip8) -> Result<(), MovieError> {
+    let mut player = try!(Player::start(movie, c8));
+    while !player.done() {
+        for _ in 0..player.steps_per_frame() {
+            player.apply(c8);
+            assert!(c8.step().unwrap() != StepOutcome::Exited);
+            player.stepped();
+        }
+        c8.tick();
+        player.ticked();
+    }
+    player.verify(c8)
+}
+
+#[test]
+fn text_roundtrip() {
+    let movie = record();
+    assert_eq!(movie.events.len(), 10);
+    assert_eq!(movie.frames, 20);
+    assert_eq!(movie.cycles, 200);
+    let text = movie.to_text();
+    assert!(text.starts_with("chip8-movie 1\n"));
+    assert!(text.contains("\nkey 1 10 0 down\n"));
+    assert_eq!(Movie::parse(&text), Ok(movie));
+}
+
+#[test]
+fn parse_errors() {
+    assert_eq!(Movie::parse("hello"),
+               Err(MovieError::Parse {
+                   line: 1,
+                   message: String::from("not a movie file"),
+               }));
+    let text = record().to_text().replace("key 1 10 0 down", "key 1 10 G down");
+    match Movie::parse(&text) {
+        Err(MovieError::Parse { line: 6, .. }) => {}
+        other => panic!("expected a parse error on line 6, got {:?}", other),
+    }
+}
+
+#[test]
+fn playback_matches() {
+    let movie = record();
+    let mut c8 = machine();
+    assert_eq!(play(movie.clone(), &mut c8), Ok(()));
+    assert!(c8.gfx.iter().any(|&p| p != 0));
+
+    // a different seed draws different sprites
+    let mut other = movie.clone();
+    other.seed = 99;
+    match play(other, &mut machine()) {
+        Err(MovieError::Desync { .. }) => {}
+        other => panic!("expected a desync, got {:?}", other),
+    }
+
+    let mut c8 = Chip8::init();
+    c8.load_rom(&[0x12, 0x00]);
+    match Player::start(movie, &mut c8) {
+        Err(MovieError::RomMismatch { .. }) => {}
+        _ => panic!("expected a ROM mismatch"),
+    }
+}
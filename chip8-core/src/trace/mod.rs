@@ -0,0 +1,161 @@
+// Execution trace, one line per instruction in a fixed column format so runs can be diffed:
+//
+//   cycle, frame, pc, opcode, disassembly | registers written | I and the timers
+//          3      0 0204 8016  v0 >>= v1                  | v0=02 vf=01       | I=0000 DT=00 ST=00
+
+use std::io::{self, Write};
+
+use chip8::{Chip8, Chip8Error, StepOutcome};
+use instruction::Instruction;
+
+#[cfg(test)]
+mod tests;
+
+pub struct Tracer<W: Write> {
+    out: W,
+    // instructions executed so far, counted whether or not they are written
+    cycle: u64,
+    // 60Hz frames so far
+    frame: u64,
+    // inclusive PC ranges to write, everything when empty
+    addresses: Vec<(u16, u16)>,
+    // inclusive range of frames to write, everything when None
+    frames: Option<(u64, u64)>,
+}
+
+/// The V registers an instruction writes, VF included where the instruction sets a flag.
+fn written_registers(c8: &Chip8, instruction: Instruction) -> Vec<u8> {
+    use instruction::Instruction::*;
+
+    match instruction {
+        LoadImm(x, _) | AddImm(x, _) | Move(x, _) | Random(x, _) | GetDelay(x) | WaitKey(x) => {
+            vec![x]
+        }
+        Or(x, _) | And(x, _) | Xor(x, _) => {
+            if c8.quirks.logic_resets_vf && x != 0xF {
+                vec![x, 0xF]
+            } else {
+                vec![x]
+            }
+        }
+        Add(x, _) | Sub(x, _) | ShiftRight(x, _) | SubReverse(x, _) | ShiftLeft(x, _) => {
+            if x == 0xF {
+                vec![x]
+            } else {
+                vec![x, 0xF]
+            }
+        }
+        // I += VX sets VF on overflow past 0xFFFF
+        Draw(..) | AddI(_) => vec![0xF],
+        LoadRange(x, y) => {
+            if x <= y {
+                (x..y + 1).collect()
+            } else {
+                (y..x + 1).rev().collect()
+            }
+        }
+        Load(x) | LoadFlags(x) => (0..x + 1).collect(),
+        _ => Vec::new(),
+    }
+}
+
+impl<W: Write> Tracer<W> {
+    pub fn new(out: W) -> Tracer<W> {
+        Tracer {
+            out: out,
+            cycle: 0,
+            frame: 0,
+            addresses: Vec::new(),
+            frames: None,
+        }
+    }
+
+    /// Only write instructions with a PC in `start..=end`. Can be called more than once.
+    pub fn filter_addresses(&mut self, start: u16, end: u16) {
+        self.addresses.push((start, end));
+    }
+
+    /// Only write instructions executed in frames `first..=last`.
+    pub fn filter_frames(&mut self, first: u64, last: u64) {
+        self.frames = Some((first, last));
+    }
+
+    #[inline]
+    pub fn cycle(&self) -> u64 {
+        self.cycle
+    }
+
+    /// Counts a 60Hz frame, call it alongside `Chip8::tick`.
+    pub fn frame(&mut self) {
+        self.frame += 1;
+    }
+
+    fn wanted(&self, pc: u16) -> bool {
+        let in_frames = match self.frames {
+            Some((first, last)) => self.frame >= first && self.frame <= last,
+            None => true,
+        };
+        let in_addresses = self.addresses.is_empty() ||
+                           self.addresses.iter().any(|&(start, end)| pc >= start && pc <= end);
+        in_frames && in_addresses
+    }
+
+    /// Runs `step` on the machine and writes a line if it executed an instruction. `step` is
+    /// usually `Chip8::step`, or a debugger wrapping it that returns None when it didn't run.
+    pub fn step<F>(&mut self,
+                   c8: &mut Chip8,
+                   step: F)
+                   -> io::Result<Result<Option<StepOutcome>, Chip8Error>>
+        where F: FnOnce(&mut Chip8) -> Result<Option<StepOutcome>, Chip8Error>
+    {
+        let pc = c8.pc();
+        let op = c8.current_opcode();
+        // read before stepping, the PC has moved past the address of a long load afterwards
+        let long = c8.memory()
+                     .get(pc as usize + 2..pc as usize + 4)
+                     .map(|b| (b[0] as u16) << 8 | b[1] as u16);
+
+        let result = step(c8);
+        match result {
+            Ok(Some(StepOutcome::Executed)) |
+            Ok(Some(StepOutcome::Exited)) => {}
+            _ => return Ok(result),
+        }
+        self.cycle += 1;
+        if !self.wanted(pc) {
+            return Ok(result);
+        }
+
+        let op = op.unwrap_or(0);
+        let (text, written) = match Instruction::decode(op) {
+            Some(Instruction::LoadLongI) => {
+                (format!("i := long 0x{:04X}", long.unwrap_or(0)), Vec::new())
+            }
+            Some(i) => (format!("{}", i), written_registers(c8, i)),
+            None => (String::from("?"), Vec::new()),
+        };
+        let registers: Vec<String> = written.iter()
+                                            .map(|&x| {
+                                                format!("v{:x}={:02X}", x, c8.v()[x as usize])
+                                            })
+                                            .collect();
+        try!(writeln!(self.out,
+                      "{:>10} {:>6} {:04X} {:04X}  {:<26} | {:<17} | I={:04X} DT={:02X} ST={:02X}",
+                      self.cycle,
+                      self.frame,
+                      pc,
+                      op,
+                      text,
+                      registers.join(" "),
+                      c8.i(),
+                      c8.delay_timer(),
+                      c8.sound_timer));
+        Ok(result)
+    }
+
+    /// Flushes buffered lines, and hands back the writer.
+    pub fn into_inner(mut self) -> io::Result<W> {
+        try!(self.out.flush());
+        Ok(self.out)
+    }
+}
@@ -0,0 +1,72 @@
+use super::*;
+use asm::assemble;
+
+fn machine() -> Chip8 {
+    let mut c8 = Chip8::init();
+    c8.load_rom(&assemble("v0 := 5\n\
+                           v1 := 3\n\
+                           v0 >>= v1\n\
+                           i := 0x234\n\
+                           exit")
+                    .unwrap());
+    c8
+}
+
+fn run(tracer: &mut Tracer<Vec<u8>>, c8: &mut Chip8, steps: usize) {
+    for _ in 0..steps {
+        tracer.step(c8, |c8| c8.step().map(Some)).unwrap().unwrap();
+    }
+}
+
+#[test]
+fn trace_lines() {
+    let mut c8 = machine();
+    let mut tracer = Tracer::new(Vec::new());
+    run(&mut tracer, &mut c8, 5);
+    let trace = String::from_utf8(tracer.into_inner().unwrap()).unwrap();
+    let lines: Vec<&str> = trace.lines().collect();
+    assert_eq!(lines.len(), 5);
+    assert_eq!(lines[0],
+               "         1      0 0200 6005  v0 := 0x05                 | v0=05             | \
+                I=0000 DT=00 ST=00");
+    assert_eq!(lines[2],
+               "         3      0 0204 8016  v0 >>= v1                  | v0=02 vf=01       | \
+                I=0000 DT=00 ST=00");
+    assert_eq!(lines[4],
+               "         5      0 0208 00FD  exit                       |                   | \
+                I=0234 DT=00 ST=00");
+}
+
+#[test]
+fn trace_add_i_flag() {
+    let mut c8 = Chip8::init();
+    c8.load_rom(&assemble("i := 0xFFF\nv0 := 2\ni += v0").unwrap());
+    let mut tracer = Tracer::new(Vec::new());
+    run(&mut tracer, &mut c8, 3);
+    let trace = String::from_utf8(tracer.into_inner().unwrap()).unwrap();
+    assert!(trace.lines().nth(2).unwrap().contains("| vf=00 "), "{}", trace);
+}
+
+#[test]
+fn trace_filters() {
+    let mut c8 = machine();
+    let mut tracer = Tracer::new(Vec::new());
+    tracer.filter_addresses(0x202, 0x204);
+    tracer.filter_frames(1, 1);
+    run(&mut tracer, &mut c8, 2);
+    tracer.frame();
+    run(&mut tracer, &mut c8, 3);
+    assert_eq!(tracer.cycle(), 5);
+    let trace = String::from_utf8(tracer.into_inner().unwrap()).unwrap();
+    assert_eq!(trace.lines().count(), 1);
+    assert!(trace.starts_with("         3      1 0204 8016"));
+}
+
+#[test]
+fn skipped_steps_are_not_traced() {
+    let mut c8 = machine();
+    let mut tracer = Tracer::new(Vec::new());
+    assert_eq!(tracer.step(&mut c8, |_| Ok(None)).unwrap(), Ok(None));
+    assert_eq!(tracer.cycle(), 0);
+    assert!(tracer.into_inner().unwrap().is_empty());
+}
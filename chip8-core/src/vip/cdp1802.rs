@@ -0,0 +1,356 @@
+// RCA CDP1802 CPU, the processor in the COSMAC VIP.
+//
+// Sixteen 16 bit registers, any of which can be the program counter (selected by P) or the
+// data pointer (selected by X), an 8 bit accumulator D with a carry DF, and a single output
+// flag Q. Instructions take 2 machine cycles, long branches and skips 3. Interrupts and DMA are
+// requested by the board between instructions.
+
+/// What the CPU is wired to.
+pub trait Bus {
+    fn read(&mut self, addr: u16) -> u8;
+    fn write(&mut self, addr: u16, value: u8);
+    /// 0x69-0x6F, INP 1-7.
+    fn input(&mut self, port: u8) -> u8;
+    /// 0x61-0x67, OUT 1-7.
+    fn output(&mut self, port: u8, value: u8);
+    /// Whether external flag line 1-4 is asserted.
+    fn flag(&self, n: u8) -> bool;
+}
+
+pub struct Cdp1802 {
+    pub r: [u16; 16],
+    // register selected as the program counter
+    pub p: u8,
+    // register selected as the data pointer
+    pub x: u8,
+    pub d: u8,
+    pub df: bool,
+    // X and P saved by an interrupt or MARK
+    pub t: u8,
+    // interrupt enable
+    pub ie: bool,
+    pub q: bool,
+    // set by IDL, execution stops until an interrupt or DMA
+    pub idle: bool,
+}
+
+impl Cdp1802 {
+    /// The CPU as it comes out of reset: P, X and R0 are zero, interrupts are enabled and Q is
+    /// off. Everything else is undefined on the real chip and zero here.
+    pub fn new() -> Cdp1802 {
+        Cdp1802 {
+            r: [0; 16],
+            p: 0,
+            x: 0,
+            d: 0,
+            df: false,
+            t: 0,
+            ie: true,
+            q: false,
+            idle: false,
+        }
+    }
+
+    #[inline]
+    pub fn pc(&self) -> u16 {
+        self.r[self.p as usize]
+    }
+
+    // The byte at R(P), moving past it
+    fn immediate<B: Bus>(&mut self, bus: &mut B) -> u8 {
+        let pc = self.pc();
+        self.r[self.p as usize] = pc.wrapping_add(1);
+        bus.read(pc)
+    }
+
+    // M(R(X))
+    fn rx<B: Bus>(&self, bus: &mut B) -> u8 {
+        bus.read(self.r[self.x as usize])
+    }
+
+    fn inc(&mut self, n: u8) {
+        self.r[n as usize] = self.r[n as usize].wrapping_add(1);
+    }
+
+    fn dec(&mut self, n: u8) {
+        self.r[n as usize] = self.r[n as usize].wrapping_sub(1);
+    }
+
+    // D = a + b + carry, DF = carry out
+    fn add(&mut self, a: u8, b: u8, carry: bool) {
+        let sum = a as u16 + b as u16 + carry as u16;
+        self.d = sum as u8;
+        self.df = sum > 0xFF;
+    }
+
+    // D = a - b - borrow, DF = no borrow
+    fn sub(&mut self, a: u8, b: u8, borrow: bool) {
+        let diff = a as i16 - b as i16 - borrow as i16;
+        self.d = diff as u8;
+        self.df = diff >= 0;
+    }
+
+    // Short branches replace the low byte of R(P) with the immediate byte
+    fn short_branch<B: Bus>(&mut self, bus: &mut B, taken: bool) {
+        let pc = self.pc();
+        self.r[self.p as usize] = if taken {
+            (pc & 0xFF00) | bus.read(pc) as u16
+        } else {
+            pc.wrapping_add(1)
+        };
+    }
+
+    // Long branches load R(P) with the two immediate bytes
+    fn long_branch<B: Bus>(&mut self, bus: &mut B, taken: bool) {
+        let pc = self.pc();
+        self.r[self.p as usize] = if taken {
+            (bus.read(pc) as u16) << 8 | bus.read(pc.wrapping_add(1)) as u16
+        } else {
+            pc.wrapping_add(2)
+        };
+    }
+
+    fn long_skip(&mut self, taken: bool) {
+        if taken {
+            let pc = self.pc();
+            self.r[self.p as usize] = pc.wrapping_add(2);
+        }
+    }
+
+    // The condition tested by branch opcodes 0x30-0x3F and 0xC0-0xCF, low three bits only
+    fn condition<B: Bus>(&self, bus: &B, n: u8) -> bool {
+        match n & 7 {
+            0 => true,
+            1 => self.q,
+            2 => self.d == 0,
+            3 => self.df,
+            flag => bus.flag(flag - 3),
+        }
+    }
+
+    /// Executes one instruction and returns the machine cycles it took. Does nothing while
+    /// idle.
+    pub fn step<B: Bus>(&mut self, bus: &mut B) -> u32 {
+        if self.idle {
+            return 2;
+        }
+        let op = self.immediate(bus);
+        let n = op & 0xF;
+        match op >> 4 {
+            0x0 => {
+                if n == 0 {
+                    // IDL
+                    self.idle = true;
+                } else {
+                    // LDN
+                    self.d = bus.read(self.r[n as usize]);
+                }
+            }
+            0x1 => self.inc(n),
+            0x2 => self.dec(n),
+            0x3 => {
+                // 0x38 is SKP, the short branch that never branches. 0x39-0x3F negate 0x31-0x37
+                let taken = match n {
+                    8 => false,
+                    _ => self.condition(bus, n) != (n > 8),
+                };
+                self.short_branch(bus, taken);
+            }
+            0x4 => {
+                // LDA
+                self.d = bus.read(self.r[n as usize]);
+                self.inc(n);
+            }
+            0x5 => bus.write(self.r[n as usize], self.d),
+            0x6 => {
+                match n {
+                    // IRX
+                    0 => {
+                        let x = self.x;
+                        self.inc(x);
+                    }
+                    // OUT
+                    1...7 => {
+                        let (value, x) = (self.rx(bus), self.x);
+                        bus.output(n, value);
+                        self.inc(x);
+                    }
+                    // unused on the 1802
+                    8 => {}
+                    // INP
+                    _ => {
+                        let value = bus.input(n - 8);
+                        bus.write(self.r[self.x as usize], value);
+                        self.d = value;
+                    }
+                }
+            }
+            0x7 => self.execute_7(bus, n),
+            0x8 => self.d = self.r[n as usize] as u8,
+            0x9 => self.d = (self.r[n as usize] >> 8) as u8,
+            0xA => self.r[n as usize] = (self.r[n as usize] & 0xFF00) | self.d as u16,
+            0xB => self.r[n as usize] = (self.r[n as usize] & 0x00FF) | (self.d as u16) << 8,
+            0xC => {
+                match n {
+                    // NOP
+                    4 => {}
+                    // LSKP, LSNQ, LSNZ, LSNF and negations: LSIE, LSQ, LSZ, LSDF
+                    5...8 | 0xC...0xF => {
+                        let taken = match n {
+                            8 => true,
+                            0xC => self.ie,
+                            _ => self.condition(bus, n & 3) != (n < 8),
+                        };
+                        self.long_skip(taken);
+                    }
+                    // LBR, LBQ, LBZ, LBDF and their negations
+                    _ => {
+                        let taken = self.condition(bus, n & 3) != (n > 8);
+                        self.long_branch(bus, taken);
+                    }
+                }
+                return 3;
+            }
+            0xD => self.p = n,
+            0xE => self.x = n,
+            _ => self.execute_f(bus, n),
+        }
+        2
+    }
+
+    fn execute_7<B: Bus>(&mut self, bus: &mut B, n: u8) {
+        match n {
+            // RET and DIS
+            0 | 1 => {
+                let (value, x) = (self.rx(bus), self.x);
+                self.inc(x);
+                self.x = value >> 4;
+                self.p = value & 0xF;
+                self.ie = n == 0;
+            }
+            // LDXA
+            2 => {
+                let x = self.x;
+                self.d = self.rx(bus);
+                self.inc(x);
+            }
+            // STXD
+            3 => {
+                let x = self.x;
+                bus.write(self.r[x as usize], self.d);
+                self.dec(x);
+            }
+            // ADC
+            4 => {
+                let (m, d, df) = (self.rx(bus), self.d, self.df);
+                self.add(m, d, df);
+            }
+            // SDB
+            5 => {
+                let (m, d, df) = (self.rx(bus), self.d, self.df);
+                self.sub(m, d, !df);
+            }
+            // SHRC
+            6 => {
+                let d = self.d;
+                self.d = d >> 1 | (self.df as u8) << 7;
+                self.df = d & 1 != 0;
+            }
+            // SMB
+            7 => {
+                let (m, d, df) = (self.rx(bus), self.d, self.df);
+                self.sub(d, m, !df);
+            }
+            // SAV
+            8 => bus.write(self.r[self.x as usize], self.t),
+            // MARK
+            9 => {
+                self.t = self.x << 4 | self.p;
+                bus.write(self.r[2], self.t);
+                self.x = self.p;
+                self.dec(2);
+            }
+            // REQ and SEQ
+            0xA => self.q = false,
+            0xB => self.q = true,
+            // ADCI
+            0xC => {
+                let (m, d, df) = (self.immediate(bus), self.d, self.df);
+                self.add(m, d, df);
+            }
+            // SDBI
+            0xD => {
+                let (m, d, df) = (self.immediate(bus), self.d, self.df);
+                self.sub(m, d, !df);
+            }
+            // SHLC
+            0xE => {
+                let d = self.d;
+                self.d = d << 1 | self.df as u8;
+                self.df = d & 0x80 != 0;
+            }
+            // SMBI
+            _ => {
+                let (m, d, df) = (self.immediate(bus), self.d, self.df);
+                self.sub(d, m, !df);
+            }
+        }
+    }
+
+    // 0xF0-0xF7 operate on M(R(X)), 0xF8-0xFF on the immediate byte, except for the shifts
+    fn execute_f<B: Bus>(&mut self, bus: &mut B, n: u8) {
+        if n == 6 || n == 0xE {
+            let d = self.d;
+            if n == 6 {
+                // SHR
+                self.d = d >> 1;
+                self.df = d & 1 != 0;
+            } else {
+                // SHL
+                self.d = d << 1;
+                self.df = d & 0x80 != 0;
+            }
+            return;
+        }
+        let m = if n < 8 {
+            self.rx(bus)
+        } else {
+            self.immediate(bus)
+        };
+        let d = self.d;
+        match n & 7 {
+            // LDX and LDI
+            0 => self.d = m,
+            1 => self.d = m | d,
+            2 => self.d = m & d,
+            3 => self.d = m ^ d,
+            4 => self.add(m, d, false),
+            // SD, D = M - D
+            5 => self.sub(m, d, false),
+            // SM, D = D - M
+            _ => self.sub(d, m, false),
+        }
+    }
+
+    /// Takes an interrupt if they're enabled, saving X and P in T and continuing with R1 as
+    /// the program counter and R2 as the data pointer. Returns the machine cycles it took.
+    pub fn interrupt(&mut self) -> u32 {
+        if !self.ie {
+            return 0;
+        }
+        self.t = self.x << 4 | self.p;
+        self.p = 1;
+        self.x = 2;
+        self.ie = false;
+        self.idle = false;
+        1
+    }
+
+    /// One DMA out cycle: returns the byte at R0 and moves R0 on.
+    pub fn dma_out<B: Bus>(&mut self, bus: &mut B) -> u8 {
+        let value = bus.read(self.r[0]);
+        self.inc(0);
+        self.idle = false;
+        value
+    }
+}
@@ -0,0 +1,203 @@
+// COSMAC VIP: a CDP1802 with 4K of RAM, a hex keypad and a CDP1861 video chip, running a CHIP-8
+// interpreter image instead of emulating CHIP-8 directly.
+//
+// The 1861 draws 262 lines a frame at 14 machine cycles a line. It requests an interrupt two
+// lines before the 128 display lines, and on each display line takes 8 cycles of DMA to read a
+// line of 64 pixels from R0. The interpreter's interrupt routine points R0 at its display
+// buffer and resets it after each line so every CHIP-8 row is shown on four lines. EF1 is
+// asserted for the four lines before the display starts and ends.
+//
+// The interpreter is loaded at 0x000 and the program at 0x200, and execution starts at 0x000
+// the way the VIP's monitor starts a program in RAM. The monitor itself is not emulated.
+
+mod cdp1802;
+
+pub use self::cdp1802::{Bus, Cdp1802};
+
+#[cfg(test)]
+mod tests;
+
+const RAM_SIZE: usize = 4096;
+
+const WIDTH: usize = 64;
+const HEIGHT: usize = 128;
+
+const CYCLES_PER_LINE: i32 = 14;
+const LINES: usize = 262;
+const INTERRUPT_LINE: usize = 78;
+const DISPLAY_START: usize = 80;
+const DISPLAY_END: usize = DISPLAY_START + HEIGHT;
+// bytes of DMA per display line
+const DMA_BYTES: usize = WIDTH / 8;
+
+// Everything on the CPU's bus
+struct Board {
+    ram: [u8; RAM_SIZE],
+    // set by INP 1 and cleared by OUT 1
+    display_on: bool,
+    // EF1, driven by the 1861 around the display
+    display_flag: bool,
+    // key selected with OUT 2, EF3 shows whether it's held
+    key_latch: u8,
+    keys: [bool; 16],
+}
+
+impl Bus for Board {
+    // RAM is mirrored through the lower 32K, the monitor ROM above it isn't there
+    fn read(&mut self, addr: u16) -> u8 {
+        if addr < 0x8000 {
+            self.ram[addr as usize % RAM_SIZE]
+        } else {
+            0
+        }
+    }
+
+    fn write(&mut self, addr: u16, value: u8) {
+        if addr < 0x8000 {
+            self.ram[addr as usize % RAM_SIZE] = value;
+        }
+    }
+
+    fn input(&mut self, port: u8) -> u8 {
+        if port == 1 {
+            self.display_on = true;
+        }
+        0
+    }
+
+    fn output(&mut self, port: u8, value: u8) {
+        match port {
+            1 => self.display_on = false,
+            2 => self.key_latch = value & 0xF,
+            _ => {}
+        }
+    }
+
+    fn flag(&self, n: u8) -> bool {
+        match n {
+            1 => self.display_flag,
+            3 => self.keys[self.key_latch as usize],
+            _ => false,
+        }
+    }
+}
+
+pub struct Vip {
+    cpu: Cdp1802,
+    board: Board,
+    // cycles the last instruction of a line ran into the next one
+    overrun: i32,
+
+    // one byte per pixel like Chip8::gfx, 0 or 1
+    pub gfx: [u8; 8192],
+    // set when a frame changed the picture
+    pub draw_flag: bool,
+    // nonzero while Q, which drives the VIP's tone generator, is on
+    pub sound_timer: u8,
+}
+
+impl Vip {
+    /// A VIP with `interpreter` loaded at 0x000, ready to run from there.
+    pub fn new(interpreter: &[u8]) -> Vip {
+        let mut vip = Vip {
+            cpu: Cdp1802::new(),
+            board: Board {
+                ram: [0; RAM_SIZE],
+                display_on: false,
+                display_flag: false,
+                key_latch: 0,
+                keys: [false; 16],
+            },
+            overrun: 0,
+            gfx: [0; 8192],
+            draw_flag: true,
+            sound_timer: 0,
+        };
+        vip.load(0, interpreter);
+        // the monitor leaves the top page of RAM in R1, which the interpreter uses to place
+        // its display buffer and variables
+        vip.cpu.r[1] = (RAM_SIZE - 0x100) as u16;
+        vip
+    }
+
+    /// Loads a CHIP-8 program at 0x200. Anything past the end of RAM is dropped.
+    pub fn load_rom(&mut self, rom: &[u8]) {
+        self.load(0x200, rom);
+    }
+
+    fn load(&mut self, start: usize, data: &[u8]) {
+        let end = (start + data.len()).min(RAM_SIZE);
+        self.board.ram[start..end].copy_from_slice(&data[..end - start]);
+    }
+
+    #[inline]
+    pub fn update_keys(&mut self, key: u8, pressed: bool) {
+        self.board.keys[key as usize] = pressed;
+    }
+
+    #[inline]
+    pub fn screen_dimens(&self) -> (usize, usize) {
+        (WIDTH, HEIGHT)
+    }
+
+    #[inline]
+    pub fn cpu(&self) -> &Cdp1802 {
+        &self.cpu
+    }
+
+    #[inline]
+    pub fn memory(&self) -> &[u8] {
+        &self.board.ram
+    }
+
+    /// Runs one 60Hz frame of the CPU and video chip.
+    pub fn run_frame(&mut self) {
+        for line in 0..LINES {
+            let display = self.board.display_on;
+            self.board.display_flag = display &&
+                                      (line >= DISPLAY_START - 4 && line < DISPLAY_START ||
+                                       line >= DISPLAY_END - 4 && line < DISPLAY_END);
+            let mut cycles = CYCLES_PER_LINE - self.overrun;
+
+            // the request is held for both lines before the display
+            if display && line >= INTERRUPT_LINE && line < DISPLAY_START {
+                cycles -= self.cpu.interrupt() as i32;
+            }
+            if line >= DISPLAY_START && line < DISPLAY_END {
+                self.dma_line(line - DISPLAY_START, display);
+                if display {
+                    cycles -= DMA_BYTES as i32;
+                }
+            }
+
+            while cycles > 0 {
+                if self.cpu.idle {
+                    cycles = 0;
+                    break;
+                }
+                cycles -= self.cpu.step(&mut self.board) as i32;
+            }
+            self.overrun = -cycles;
+        }
+        self.sound_timer = self.cpu.q as u8;
+    }
+
+    // Reads a display line through DMA, or blanks it while the display is off
+    fn dma_line(&mut self, row: usize, display: bool) {
+        for byte in 0..DMA_BYTES {
+            let value = if display {
+                self.cpu.dma_out(&mut self.board)
+            } else {
+                0
+            };
+            for bit in 0..8 {
+                let pixel = (value >> (7 - bit)) & 1;
+                let pos = row * WIDTH + byte * 8 + bit;
+                if self.gfx[pos] != pixel {
+                    self.gfx[pos] = pixel;
+                    self.draw_flag = true;
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,220 @@
+use super::*;
+
+// 64K of RAM with every flag line asserted by choice
+struct TestBus {
+    ram: Vec<u8>,
+    flags: [bool; 5],
+    output: Vec<(u8, u8)>,
+}
+
+fn cpu_with(program: &[u8]) -> (Cdp1802, TestBus) {
+    let mut ram = vec![0; 0x10000];
+    ram[..program.len()].copy_from_slice(program);
+    (Cdp1802::new(),
+     TestBus {
+        ram: ram,
+        flags: [false; 5],
+        output: Vec::new(),
+    })
+}
+
+impl Bus for TestBus {
+    fn read(&mut self, addr: u16) -> u8 {
+        self.ram[addr as usize]
+    }
+
+    fn write(&mut self, addr: u16, value: u8) {
+        self.ram[addr as usize] = value;
+    }
+
+    fn input(&mut self, port: u8) -> u8 {
+        port * 0x11
+    }
+
+    fn output(&mut self, port: u8, value: u8) {
+        self.output.push((port, value));
+    }
+
+    fn flag(&self, n: u8) -> bool {
+        self.flags[n as usize]
+    }
+}
+
+fn run(cpu: &mut Cdp1802, bus: &mut TestBus, instructions: usize) -> u32 {
+    (0..instructions).map(|_| cpu.step(bus)).sum()
+}
+
+#[test]
+fn cpu_registers_and_arithmetic() {
+    let (mut cpu, mut bus) = cpu_with(&[0xF8, 0x12, // LDI 12
+                                        0xB3, // PHI R3
+                                        0xF8, 0x34, // LDI 34
+                                        0xA3, // PLO R3
+                                        0x13, // INC R3
+                                        0xF8, 0xF0, // LDI F0
+                                        0xFC, 0x20, // ADI 20
+                                        0x7C, 0x00, // ADCI 00
+                                        0xFF, 0x12, // SMI 12
+                                        0xFE, // SHL
+                                        0x76 /* SHRC */]);
+    assert_eq!(run(&mut cpu, &mut bus, 5), 10);
+    assert_eq!(cpu.r[3], 0x1235);
+    cpu.step(&mut bus);
+    assert_eq!((cpu.d, cpu.df), (0xF0, false));
+    cpu.step(&mut bus);
+    assert_eq!((cpu.d, cpu.df), (0x10, true));
+    cpu.step(&mut bus);
+    assert_eq!((cpu.d, cpu.df), (0x11, false));
+    // 0x11 - 0x12 borrows
+    cpu.step(&mut bus);
+    assert_eq!((cpu.d, cpu.df), (0xFF, false));
+    cpu.step(&mut bus);
+    assert_eq!((cpu.d, cpu.df), (0xFE, true));
+    cpu.step(&mut bus);
+    assert_eq!((cpu.d, cpu.df), (0xFF, false));
+}
+
+#[test]
+fn cpu_memory_and_io() {
+    let (mut cpu, mut bus) = cpu_with(&[0xF8, 0x80, // LDI 80
+                                        0xA5, // PLO R5
+                                        0xE5, // SEX R5
+                                        0xF8, 0x42, // LDI 42
+                                        0x73, // STXD
+                                        0x60, // IRX
+                                        0xF8, 0x00, // LDI 00
+                                        0xF0, // LDX
+                                        0x62, // OUT 2
+                                        0x6B, // INP 3
+                                        0x45 /* LDA R5 */]);
+    run(&mut cpu, &mut bus, 8);
+    assert_eq!(bus.ram[0x80], 0x42);
+    assert_eq!((cpu.d, cpu.r[5]), (0x42, 0x80));
+    cpu.step(&mut bus);
+    assert_eq!(bus.output, vec![(2, 0x42)]);
+    assert_eq!(cpu.r[5], 0x81);
+    cpu.step(&mut bus);
+    assert_eq!((cpu.d, bus.ram[0x81]), (0x33, 0x33));
+    cpu.step(&mut bus);
+    assert_eq!((cpu.d, cpu.r[5]), (0x33, 0x82));
+}
+
+#[test]
+fn cpu_branches() {
+    let (mut cpu, mut bus) = cpu_with(&[0x32, 0x05, // BZ 05, D is 0
+                                        0x00,
+                                        0x00,
+                                        0x00,
+                                        0x7B, // SEQ
+                                        0x39, 0x00, // BNQ 00
+                                        0xC5, // LSNQ
+                                        0xCD, // LSQ
+                                        0x00,
+                                        0x00,
+                                        0x34, 0x00, // B1 00
+                                        0xC0, 0x12, 0x34 /* LBR 1234 */]);
+    cpu.step(&mut bus);
+    assert_eq!(cpu.pc(), 0x05);
+    run(&mut cpu, &mut bus, 2);
+    assert_eq!(cpu.pc(), 0x08);
+    // LSNQ doesn't skip with Q set, LSQ does
+    assert_eq!(cpu.step(&mut bus), 3);
+    assert_eq!(cpu.pc(), 0x09);
+    cpu.step(&mut bus);
+    assert_eq!(cpu.pc(), 0x0C);
+    bus.flags[1] = false;
+    cpu.step(&mut bus);
+    assert_eq!(cpu.pc(), 0x0E);
+    assert_eq!(cpu.step(&mut bus), 3);
+    assert_eq!(cpu.pc(), 0x1234);
+}
+
+#[test]
+fn cpu_subroutines_and_interrupts() {
+    // SEP R4 into a routine that returns with SEP R3
+    let mut program = vec![0; 0x40];
+    program[..9].copy_from_slice(&[0xF8, 0x20, 0xA4, // LDI 20, PLO R4
+                                   0xF8, 0x07, 0xA3, // LDI 07, PLO R3
+                                   0xD3, // SEP R3
+                                   0xD4, // SEP R4
+                                   0x00]);
+    program[0x20] = 0xD3;
+    let (mut cpu, mut bus) = cpu_with(&program);
+    run(&mut cpu, &mut bus, 5);
+    assert_eq!((cpu.p, cpu.pc()), (3, 0x07));
+    cpu.step(&mut bus);
+    assert_eq!((cpu.p, cpu.pc()), (4, 0x20));
+    cpu.step(&mut bus);
+    assert_eq!((cpu.p, cpu.pc()), (3, 0x08));
+
+    // an interrupt saves X and P in T and runs R1, SAV and RET put them back
+    cpu.x = 5;
+    cpu.r[1] = 0x30;
+    cpu.r[2] = 0x3F;
+    bus.ram[0x30..0x33].copy_from_slice(&[0x22, 0x78, 0x70]); // DEC R2, SAV, RET
+    assert_eq!(cpu.interrupt(), 1);
+    assert_eq!((cpu.p, cpu.x, cpu.t, cpu.ie), (1, 2, 0x53, false));
+    // disabled now, so a second request is ignored
+    assert_eq!(cpu.interrupt(), 0);
+    run(&mut cpu, &mut bus, 3);
+    assert_eq!((cpu.p, cpu.x, cpu.ie), (3, 5, true));
+    assert_eq!(cpu.r[2], 0x3F);
+}
+
+// Turns the display on and shows 0x0E00 onwards, one byte row per line
+fn display_program() -> Vec<u8> {
+    let mut interpreter = vec![0; 0x200];
+    interpreter[..0x0F].copy_from_slice(&[0xF8, 0x00, 0xB1, 0xB2, 0xB3, // R1-R3.1 = 0
+                                          0xF8, 0x22, 0xA1, // R1.0 = 22
+                                          0xF8, 0xFF, 0xA2, // R2.0 = FF
+                                          0xF8, 0x10, 0xA3, // R3.0 = 10
+                                          0xD3 /* SEP R3, R0 is for DMA */]);
+    interpreter[0x10..0x14].copy_from_slice(&[0xE2, // SEX R2
+                                              0x69, // INP 1
+                                              0x30, 0x12 /* BR 12 */]);
+    interpreter[0x20..0x2E].copy_from_slice(&[0x72, 0x70, // LDXA, RET
+                                              0x22, 0x78, 0x22, 0x52, // save T and D
+                                              0xF8, 0x0E, 0xB0, // LDI 0E, PHI R0
+                                              0xF8, 0x00, 0xA0, // LDI 00, PLO R0
+                                              0x30, 0x20 /* BR 20 */]);
+    interpreter
+}
+
+#[test]
+fn vip_display_dma() {
+    let mut vip = Vip::new(&display_program());
+    for byte in vip.board.ram[0xE00..0xE08].iter_mut() {
+        *byte = 0xFF;
+    }
+    vip.board.ram[0xE09] = 0x81;
+    vip.run_frame();
+    assert!(vip.board.display_on);
+    assert!(vip.draw_flag);
+    assert!(vip.gfx[..64].iter().all(|&p| p == 1));
+    assert!(vip.gfx[64..72].iter().all(|&p| p == 0));
+    assert_eq!(&vip.gfx[72..80], &[1, 0, 0, 0, 0, 0, 0, 1]);
+    // R0 isn't reset after each line, so the rest of the page follows
+    assert!(vip.gfx[128..64 * 32].iter().all(|&p| p == 0));
+
+    // nothing changed on the next frame
+    vip.draw_flag = false;
+    vip.run_frame();
+    assert!(!vip.draw_flag);
+    assert_eq!(vip.cpu().pc(), 0x12);
+}
+
+#[test]
+fn vip_keypad_and_tone() {
+    let mut vip = Vip::new(&[0x7B, 0x30, 0x01]); // SEQ, BR 01
+    vip.load_rom(&[0x12, 0x34]);
+    assert_eq!(&vip.memory()[0x200..0x202], &[0x12, 0x34]);
+    vip.run_frame();
+    assert_eq!(vip.sound_timer, 1);
+
+    vip.board.output(2, 0xA5);
+    assert!(!vip.board.flag(3));
+    vip.update_keys(5, true);
+    assert!(vip.board.flag(3));
+    vip.update_keys(5, false);
+    assert!(!vip.board.flag(3));
+}
@@ -1,11 +1,36 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufRead, BufWriter, Read, Write};
+use std::mem;
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+
 use fps_counter::FPSCounter;
-use opengl_graphics::*;
 use piston::input::*;
 use rodio::{self, Source};
 
-use chip8::Chip8;
+use audio::PatternWave;
+use display::{Display, Indicator, RGBA};
+use gdbserver::GdbServer;
+use keymap::Keymap;
+use phosphor::Phosphor;
+use watch::FileWatch;
+
+use chip8_core::{Chip8, Chip8Error, Quirks, StepOutcome};
+use chip8_core::debugger::{Command, Debugger};
+use chip8_core::trace::Tracer;
+use chip8_core::movie::{Movie, MovieError, Player, Recorder};
+use chip8_core::vip::Vip;
+use rewind::Rewind;
+
+// Memory budget for the rewind history, enough for several minutes of typical gameplay
+const REWIND_BUDGET: usize = 32 * 1024 * 1024;
+
+// Speed while slow motion is on, as a multiple of normal speed
+const SLOW_MOTION: f64 = 0.25;
 
-type RGBA = [f32; 4];
+// Most time in seconds a single update catches up on, four frames
+const MAX_CATCH_UP: f64 = 4.0 / 60.0;
 
 trait RGBATrait {
     fn rgba(r: f32, g: f32, b: f32, a: f32) -> RGBA;
@@ -31,105 +56,595 @@ impl RGBATrait for RGBA {
 }
 
 pub struct App {
-    gl: GlGraphics,
+    display: Box<Display>,
     c8: Chip8,
     ticker: f64,
     fps_counter: FPSCounter,
-    clock_counter: FPSCounter,
     lastfps: usize,
-    lasthz: usize,
-    clockspeed: usize,
-    background_color: RGBA,
-    foreground_color: RGBA,
+    // instructions run per 60Hz frame
+    ipf: usize,
+    // colour for each pixel value: background, plane 1, plane 2 and both planes
+    palette: [RGBA; 4],
+    audio_endpoint: rodio::Endpoint,
     audio: rodio::Sink,
+    // XO-CHIP pattern and pitch the sink is currently playing, None for the plain beep
+    audio_pattern: Option<([u8; 16], u8)>,
+    quirks: Quirks,
+    // seed for 0xCXNN, applied on every reload so runs repeat exactly
+    seed: Option<u64>,
+    no_overdraw: bool,
+    program_file: String,
+    // the ROM as last read from program_file, reloads and resets start over with it
+    rom: Vec<u8>,
+    // set once the interpreter exits or faults, stops further execution
+    halted: bool,
+    rewind: Rewind,
+    // true while the rewind key is held, frames are then played backwards
+    rewinding: bool,
+    // set with --debug, execution then goes through the debugger
+    debugger: Option<Debugger>,
+    // lines typed into the debugger REPL on stdin
+    debug_commands: Option<Receiver<String>>,
+    // set with --gdb, execution then goes through the attached gdb if there is one
+    gdb: Option<GdbServer>,
+    // set with --trace
+    tracer: Option<Tracer<BufWriter<File>>>,
+    input: Input,
+    keymap: Keymap,
+    // hex key held by each gamepad axis that is past the threshold
+    axes: HashMap<u8, u8>,
+    // toggled with P, nothing runs until it's toggled back or a frame is advanced
+    paused: bool,
+    // set with the period key while paused, runs one frame on the next update
+    advance: bool,
+    // true while tab is held
+    fast_forwarding: bool,
+    // multiple of normal speed to run at while fast-forwarding, set with --fast-forward
+    fast_forward: f64,
+    // toggled with the backquote key
+    slow_motion: bool,
+    // set with --watch, reloads the ROM whenever it's rebuilt
+    watch: Option<FileWatch>,
+    // set with --restart-pc, reloads go back to the state the machine was in when it first got
+    // here instead of starting over
+    restart_pc: Option<u16>,
+    restart_state: Option<Vec<u8>>,
+    // set with --vip, the interpreter image the ROM runs under instead of c8
+    vip_interpreter: Option<Vec<u8>>,
+    vip: Option<Vip>,
+    // set with --persistence, pixels then fade out instead of going straight off
+    phosphor: Option<Phosphor>,
+}
+
+/// What --record or --play asked for.
+pub enum MovieOption {
+    Off,
+    Record { path: String, seed: u64 },
+    Play(Movie),
+}
+
+// Where keypad input comes from. Recording and playback run a fixed number of steps per frame
+// so the movie replays exactly.
+enum Input {
+    Live,
+    Recording { recorder: Recorder, path: String },
+    Playing(Player),
+}
+
+/// Reads a ROM, refusing one too big to load at 0x200 rather than letting `load_rom` panic.
+pub fn read_rom(path: &str) -> io::Result<Vec<u8>> {
+    let mut rom = Vec::new();
+    try!(File::open(path).and_then(|mut f| f.read_to_end(&mut rom)));
+    if rom.len() > 0x10000 - 0x200 {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "ROM is too large"));
+    }
+    Ok(rom)
+}
+
+// Reads stdin on its own thread so the window keeps rendering while waiting for commands
+fn spawn_repl() -> Receiver<String> {
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        let stdin = io::stdin();
+        for line in stdin.lock().lines() {
+            match line {
+                Ok(line) => {
+                    if sender.send(line).is_err() {
+                        break;
+                    }
+                }
+                Err(_) => break,
+            }
+        }
+    });
+    receiver
 }
 
 impl App {
-    pub fn init(gl: GlGraphics,
+    pub fn init(display: Box<Display>,
                 program_file: String,
-                clock: usize,
-                foreground: [u8; 4],
-                background: [u8; 4],
-                no_overdraw: bool)
+                ipf: usize,
+                palette: [[u8; 4]; 4],
+                no_overdraw: bool,
+                quirks: Quirks,
+                seed: Option<u64>,
+                debug: bool,
+                gdb: Option<GdbServer>,
+                tracer: Option<Tracer<BufWriter<File>>>,
+                keymap: Keymap,
+                fast_forward: f64)
                 -> App {
         let source = rodio::source::SineWave::new(400);
         let endp = rodio::get_endpoints_list().find(|x| x.get_name() == "pulse").unwrap_or(rodio::get_default_endpoint().unwrap());
         let mut sink = rodio::Sink::new(&endp);
         sink.set_volume(0.0);
         sink.append(source.repeat_infinite());
+        let palette = [RGBA::from_u8(palette[0]),
+                       RGBA::from_u8(palette[1]),
+                       RGBA::from_u8(palette[2]),
+                       RGBA::from_u8(palette[3])];
+
+        let rom = read_rom(&program_file).unwrap_or_else(|e| {
+            println!("Could not read {}: {}", program_file, e);
+            ::std::process::exit(1);
+        });
 
         let mut temp = App {
-            gl: gl,
+            display: display,
             c8: Chip8::init(),
             ticker: 0.0,
             fps_counter: FPSCounter::new(),
-            clock_counter: FPSCounter::new(),
             lastfps: 0,
-            lasthz: clock,
-            clockspeed: clock,
-            foreground_color: RGBA::from_u8(foreground),
-            background_color: RGBA::from_u8(background),
+            ipf: ipf,
+            palette: palette,
+            audio_endpoint: endp,
             audio: sink,
+            audio_pattern: None,
+            quirks: quirks,
+            seed: seed,
+            no_overdraw: no_overdraw,
+            program_file: program_file,
+            rom: rom,
+            halted: false,
+            rewind: Rewind::new(REWIND_BUDGET),
+            rewinding: false,
+            debugger: if debug {
+                Some(Debugger::new())
+            } else {
+                None
+            },
+            debug_commands: if debug {
+                Some(spawn_repl())
+            } else {
+                None
+            },
+            gdb: gdb,
+            tracer: tracer,
+            input: Input::Live,
+            keymap: keymap,
+            axes: HashMap::new(),
+            paused: false,
+            advance: false,
+            fast_forwarding: false,
+            fast_forward: fast_forward,
+            slow_motion: false,
+            watch: None,
+            restart_pc: None,
+            restart_state: None,
+            vip_interpreter: None,
+            vip: None,
+            phosphor: None,
         };
-        temp.c8.load_program(program_file);
-        temp.c8.no_overdraw = no_overdraw;
+        if debug {
+            println!("Debugger paused at start, type help for commands");
+        }
+        temp.reload();
         temp
     }
 
-    #[allow(dead_code)]
-    pub fn reload(&mut self, filename: String) {
+    /// Starts the ROM over on a fresh machine.
+    pub fn reload(&mut self) {
         self.c8 = Chip8::init();
-        self.c8.load_program(filename);
+        self.c8.quirks = self.quirks;
+        if let Some(seed) = self.seed {
+            self.c8.seed(seed);
+        }
+        self.c8.no_overdraw = self.no_overdraw;
+        self.c8.load_rom(&self.rom);
+        if let Some(ref interpreter) = self.vip_interpreter {
+            let mut vip = Vip::new(interpreter);
+            vip.load_rom(&self.rom);
+            self.vip = Some(vip);
+        }
+        self.halted = false;
+        self.rewind.clear();
+        self.update_audio_source();
+    }
+
+    /// Runs the ROM on an emulated COSMAC VIP under `interpreter` instead of on the built in
+    /// CHIP-8 core, which still loads the ROM but sits idle.
+    pub fn run_on_vip(&mut self, interpreter: Vec<u8>) {
+        self.vip_interpreter = Some(interpreter);
+        self.reload();
+    }
+
+    /// Fades pixels out over `frames` 60Hz frames after they go off, rather than at once.
+    pub fn set_persistence(&mut self, frames: u32) {
+        self.phosphor = Some(Phosphor::new(frames));
     }
 
     pub fn render(&mut self, args: &RenderArgs) {
-        if self.c8.draw_flag {
-            use graphics::*;
-
-            let (memwidth, memheight) = self.c8.screen_dimens();
-            let wscale = args.width as f64 / memwidth as f64;
-            let hscale = args.height as f64 / memheight as f64;
-            let fcolor = self.foreground_color;
-            let bcolor = self.background_color;
-            let mut tsettings = TextureSettings::new();
-            tsettings.set_min(Filter::Nearest);
-            tsettings.set_mag(Filter::Nearest);
-            let texture = Texture::from_memory_alpha(&self.c8.gfx,
-                                                     memwidth as u32,
-                                                     memheight as u32,
-                                                     &tsettings)
-                              .unwrap();
-
-            self.gl.draw(args.viewport(), |c, gl| {
-                clear(bcolor, gl);
-                Image::new_color(fcolor).draw(&texture,
-                                              default_draw_state(),
-                                              c.transform.scale(wscale, hscale),
-                                              gl);
-            });
+        // c8's flag also asks for a redraw when the indicator changes
+        let vip_drawn = self.vip.as_ref().map(|vip| vip.draw_flag).unwrap_or(false);
+        let fading = self.phosphor.as_ref().map(|p| p.draw_flag).unwrap_or(false);
+        if self.c8.draw_flag || vip_drawn || fading {
+            let indicator = self.indicator();
+            let palette = self.palette;
+            {
+                let (gfx, dimens) = match self.vip {
+                    Some(ref vip) => (&vip.gfx[..], vip.screen_dimens()),
+                    None => (&self.c8.gfx[..], self.c8.screen_dimens()),
+                };
+                match self.phosphor {
+                    // until the phosphor catches up with a new resolution it's drawn as is
+                    Some(ref phosphor) if phosphor.dimens() == dimens => {
+                        self.display.draw(phosphor.colors(),
+                                          Some(phosphor.intensity()),
+                                          dimens,
+                                          &palette,
+                                          indicator,
+                                          args)
+                    }
+                    _ => self.display.draw(gfx, None, dimens, &palette, indicator, args),
+                }
+            }
 
             self.c8.draw_flag = false;
+            if let Some(ref mut vip) = self.vip {
+                vip.draw_flag = false;
+            }
+            if let Some(ref mut phosphor) = self.phosphor {
+                phosphor.draw_flag = false;
+            }
         }
         self.lastfps = self.fps_counter.tick();
     }
 
     pub fn update(&mut self, args: &UpdateArgs) {
-        self.ticker += args.dt;
+        let rebuilt = match self.watch {
+            Some(ref mut watch) => watch.poll(args.dt),
+            None => false,
+        };
+        if rebuilt {
+            self.hot_reload();
+        }
+        self.run_debug_commands();
+        if let Some(ref mut gdb) = self.gdb {
+            gdb.poll(&mut self.c8);
+        }
+        let paused = self.debugger.as_ref().map(|d| d.is_paused()).unwrap_or(false) ||
+                     self.gdb.as_ref().map(|g| g.is_paused()).unwrap_or(false);
+
+        if self.paused {
+            self.audio.set_volume(0.0);
+            if self.advance && !paused {
+                self.advance_frame();
+            }
+            self.advance = false;
+            return;
+        }
+
+        // everything runs in whole frames of instructions followed by a timer tick, so the
+        // window's frame rate, fast-forward and slow motion only change how often frames run.
+        // After a stall, such as the window being dragged, the frames missed are dropped rather
+        // than run in one burst
+        self.ticker += args.dt.min(MAX_CATCH_UP) * self.speed();
         while self.ticker >= 1.0 / 60.0 {
-            self.c8.tick();
+            if paused {
+                // timers are frozen while a debugger has execution paused
+            } else if self.rewinding {
+                if let Some(state) = self.rewind.pop() {
+                    self.c8.load_state(&state).unwrap();
+                    self.halted = false;
+                }
+            } else {
+                self.frame();
+            }
+            self.glow();
             self.ticker -= 1.0 / 60.0;
         }
 
-        self.audio.set_volume(if self.c8.sound_timer > 0 {1.0} else {0.0});
+        if self.rewinding {
+            self.audio.set_volume(0.0);
+            return;
+        }
+
+        self.update_audio_source();
+        let sound_timer = match self.vip {
+            Some(ref vip) => vip.sound_timer,
+            None => self.c8.sound_timer,
+        };
+        self.audio.set_volume(if sound_timer > 0 {1.0} else {0.0});
+
+        if let Some(ref mut debugger) = self.debugger {
+            if let Some(stop) = debugger.take_stop() {
+                print!("{}", debugger.describe(&self.c8, stop));
+            }
+        }
+    }
+
+    // Runs the instructions and timers for one 60Hz frame
+    fn frame(&mut self) {
+        if let Some(ref mut vip) = self.vip {
+            vip.run_frame();
+            return;
+        }
+        let steps = self.steps_per_frame();
+        self.run_steps(steps);
+        self.c8.tick();
+        if let Some(ref mut tracer) = self.tracer {
+            tracer.frame();
+        }
+        self.movie_ticked();
+        self.rewind.push(self.c8.save_state());
+    }
+
+    // Lights and fades the phosphor for a 60Hz frame
+    fn glow(&mut self) {
+        if let Some(ref mut phosphor) = self.phosphor {
+            match self.vip {
+                Some(ref vip) => phosphor.update(&vip.gfx, vip.screen_dimens()),
+                None => phosphor.update(&self.c8.gfx, self.c8.screen_dimens()),
+            }
+        }
+    }
+
+    // Runs exactly one frame while paused
+    fn advance_frame(&mut self) {
+        self.frame();
+        self.glow();
+        // the indicator is drawn with the screen, so redraw even if the frame didn't
+        self.c8.draw_flag = true;
+    }
+
+    fn speed(&self) -> f64 {
+        if self.fast_forwarding {
+            self.fast_forward
+        } else if self.slow_motion {
+            SLOW_MOTION
+        } else {
+            1.0
+        }
+    }
+
+    fn indicator(&self) -> Indicator {
+        if self.paused {
+            Indicator::Paused
+        } else if self.fast_forwarding {
+            Indicator::FastForward
+        } else if self.slow_motion {
+            Indicator::SlowMotion
+        } else {
+            Indicator::None
+        }
+    }
+
+    // Starts the ROM over, keeping the speed and pause state
+    fn reset(&mut self) {
+        if !self.is_live() {
+            println!("Resetting is disabled while a movie is recording or playing");
+            return;
+        }
+        self.reload();
+        self.c8.draw_flag = true;
+    }
+
+    /// Reloads the ROM whenever the file changes. With `restart_pc`, reloads restore the state
+    /// captured when execution first reached it, with the new ROM swapped in.
+    pub fn watch_rom(&mut self, restart_pc: Option<u16>) {
+        self.watch = Some(FileWatch::new(self.program_file.clone()));
+        self.restart_pc = restart_pc;
+    }
+
+    fn hot_reload(&mut self) {
+        if !self.is_live() {
+            println!("Not reloading {} while a movie is recording or playing",
+                     self.program_file);
+            return;
+        }
+        let rom = match read_rom(&self.program_file) {
+            Ok(rom) => rom,
+            Err(e) => {
+                println!("Could not reload {}: {}", self.program_file, e);
+                return;
+            }
+        };
+        // the state was captured with the old ROM loaded, so restore it before swapping the new
+        // one in, and capture again so the next reload matches
+        let restored = match self.restart_state.take() {
+            Some(state) => self.c8.load_state(&state).is_ok(),
+            None => false,
+        };
+        if restored {
+            self.c8.replace_rom(self.rom.len(), &rom);
+            self.rom = rom;
+            self.restart_state = Some(self.c8.save_state());
+            self.halted = false;
+            self.rewind.clear();
+            println!("Reloaded {} at {:04X}", self.program_file, self.c8.pc());
+        } else {
+            self.rom = rom;
+            self.reload();
+            println!("Reloaded {}", self.program_file);
+        }
+    }
+
+    fn is_live(&self) -> bool {
+        match self.input {
+            Input::Live => true,
+            _ => false,
+        }
+    }
+
+    // Movies play back at the rate they were recorded at
+    fn steps_per_frame(&self) -> usize {
+        match self.input {
+            Input::Live | Input::Recording { .. } => self.ipf,
+            Input::Playing(ref player) => player.steps_per_frame() as usize,
+        }
+    }
+
+    fn run_steps(&mut self, count: usize) {
+        for _ in 0..count {
+            if self.halted {
+                break;
+            }
+            if let Input::Playing(ref mut player) = self.input {
+                player.apply(&mut self.c8);
+            }
+            match self.step_once() {
+                Ok(Some(StepOutcome::Executed)) |
+                Ok(Some(StepOutcome::WaitingForKey)) => {}
+                Ok(Some(StepOutcome::Exited)) => self.halted = true,
+                // the debugger paused before running anything, or a draw is waiting for the
+                // next frame
+                Ok(None) |
+                Ok(Some(StepOutcome::WaitingForVblank)) => break,
+                Err(e) => self.fault(e),
+            }
+            match self.input {
+                Input::Live => {}
+                Input::Recording { ref mut recorder, .. } => recorder.stepped(),
+                Input::Playing(ref mut player) => player.stepped(),
+            }
+            if self.restart_state.is_none() && Some(self.c8.pc()) == self.restart_pc {
+                self.restart_state = Some(self.c8.save_state());
+            }
+        }
+    }
+
+    // Counts a frame for the movie, and hands control back once playback is over
+    fn movie_ticked(&mut self) {
+        let finished = match self.input {
+            Input::Live => false,
+            Input::Recording { ref mut recorder, .. } => {
+                recorder.ticked();
+                false
+            }
+            Input::Playing(ref mut player) => {
+                player.ticked();
+                if player.done() {
+                    match player.verify(&self.c8) {
+                        Ok(()) => println!("Playback finished and matches the recording"),
+                        Err(e) => println!("{}", e),
+                    }
+                }
+                player.done()
+            }
+        };
+        if finished {
+            self.input = Input::Live;
+        }
+    }
+
+    /// Starts recording or playing back a movie from the start of the ROM.
+    pub fn start_movie(&mut self, option: MovieOption) -> Result<(), MovieError> {
+        self.reload();
+        self.input = match option {
+            MovieOption::Off => Input::Live,
+            MovieOption::Record { path, seed } => {
+                Input::Recording {
+                    recorder: Recorder::start(&mut self.c8, seed, self.ipf as u32),
+                    path: path,
+                }
+            }
+            MovieOption::Play(movie) => Input::Playing(try!(Player::start(movie, &mut self.c8))),
+        };
+        Ok(())
+    }
+
+    /// Writes out the movie being recorded, if there is one.
+    pub fn finish_movie(&mut self) {
+        if let Input::Recording { recorder, path } = mem::replace(&mut self.input, Input::Live) {
+            let movie = recorder.finish(&self.c8);
+            match File::create(&path).and_then(|mut f| f.write_all(movie.to_text().as_bytes())) {
+                Ok(()) => println!("Saved movie to {}", path),
+                Err(e) => println!("Could not save movie to {}: {}", path, e),
+            }
+        }
+    }
+
+    // Runs one instruction through whichever debugger is active, tracing it if asked to
+    fn step_once(&mut self) -> Result<Option<StepOutcome>, Chip8Error> {
+        let traced = {
+            let gdb = &mut self.gdb;
+            let debugger = &mut self.debugger;
+            let step = |c8: &mut Chip8| if let Some(ref mut gdb) = *gdb {
+                gdb.step(c8)
+            } else if let Some(ref mut debugger) = *debugger {
+                debugger.step(c8)
+            } else {
+                c8.step().map(Some)
+            };
+            match self.tracer {
+                Some(ref mut tracer) => tracer.step(&mut self.c8, step),
+                None => return step(&mut self.c8),
+            }
+        };
+        match traced {
+            Ok(result) => result,
+            Err(e) => {
+                println!("Could not write trace, tracing stopped: {}", e);
+                self.tracer = None;
+                Ok(None)
+            }
+        }
+    }
 
-        for _ in 0..(((self.clockspeed as f64) * args.dt).round() as usize) {
-            self.c8.step();
-            self.lasthz = self.clock_counter.tick();
+    fn run_debug_commands(&mut self) {
+        let mut lines = Vec::new();
+        match self.debug_commands {
+            Some(ref receiver) => {
+                while let Ok(line) = receiver.try_recv() {
+                    lines.push(line);
+                }
+            }
+            None => return,
         }
-        if ((self.lasthz as f64) * 0.05) + (self.lasthz as f64) < (self.clockspeed as f64) ||
-           (self.lasthz as f64) - ((self.lasthz as f64) * 0.05) > (self.clockspeed as f64) {
-            println!("CPU is out of sync: {}Hz", self.lasthz);
+        let debugger = self.debugger.as_mut().unwrap();
+        for line in lines {
+            if line.trim().is_empty() {
+                continue;
+            }
+            match Command::parse(&line) {
+                Ok(command) => print!("{}", debugger.execute(&self.c8, command)),
+                Err(e) => println!("{}", e),
+            }
         }
+        let _ = io::stdout().flush();
+    }
+
+    // Swaps the sink over to the XO-CHIP pattern whenever the program loads a new one
+    fn update_audio_source(&mut self) {
+        let wanted = self.c8.audio_pattern.map(|p| (p, self.c8.pitch));
+        if wanted == self.audio_pattern {
+            return;
+        }
+        let mut sink = rodio::Sink::new(&self.audio_endpoint);
+        sink.set_volume(0.0);
+        match wanted {
+            Some((pattern, pitch)) => sink.append(PatternWave::new(pattern, pitch)),
+            None => sink.append(rodio::source::SineWave::new(400).repeat_infinite()),
+        }
+        self.audio.stop();
+        self.audio = sink;
+        self.audio_pattern = wanted;
+    }
+
+    fn fault(&mut self, err: Chip8Error) {
+        println!("Emulation halted: {}", err);
+        self.c8.reginfo();
+        self.halted = true;
     }
 
     #[inline]
@@ -142,25 +657,121 @@ impl App {
         self.handle_keys(args, false);
     }
 
+    /// Presses the hex key for a stick direction once it passes the threshold, and releases it
+    /// when the stick comes back or moves to another direction.
+    pub fn axis(&mut self, args: &ControllerAxisArgs) {
+        let held = self.keymap.hex_axis(args.axis, args.position);
+        let previous = self.axes.get(&args.axis).cloned();
+        if held == previous {
+            return;
+        }
+        if let Some(hex) = previous {
+            self.keypad(hex, false);
+            self.axes.remove(&args.axis);
+        }
+        if let Some(hex) = held {
+            self.keypad(hex, true);
+            self.axes.insert(args.axis, hex);
+        }
+    }
+
+    // Quick-save slots live next to the ROM as <rom>.state<slot>
+    fn slot_path(&self, slot: u8) -> String {
+        format!("{}.state{}", self.program_file, slot)
+    }
+
+    pub fn quick_save(&mut self, slot: u8) {
+        if self.vip.is_some() {
+            println!("Saving states is not supported on the VIP");
+            return;
+        }
+        let path = self.slot_path(slot);
+        match File::create(&path).and_then(|mut f| f.write_all(&self.c8.save_state())) {
+            Ok(()) => println!("Saved state to {}", path),
+            Err(e) => println!("Could not save state to {}: {}", path, e),
+        }
+    }
+
+    pub fn quick_load(&mut self, slot: u8) {
+        if !self.is_live() {
+            println!("Loading states is disabled while a movie is recording or playing");
+            return;
+        }
+        if self.vip.is_some() {
+            println!("Loading states is not supported on the VIP");
+            return;
+        }
+        let path = self.slot_path(slot);
+        let mut data = Vec::new();
+        if let Err(e) = File::open(&path).and_then(|mut f| f.read_to_end(&mut data)) {
+            println!("Could not read state from {}: {}", path, e);
+            return;
+        }
+        match self.c8.load_state(&data) {
+            Ok(()) => {
+                self.halted = false;
+                println!("Loaded state from {}", path);
+            }
+            Err(e) => println!("Could not load state from {}: {}", path, e),
+        }
+    }
+
+    fn keypad(&mut self, key: u8, pressed: bool) {
+        match self.input {
+            Input::Live => {
+                match self.vip {
+                    Some(ref mut vip) => vip.update_keys(key, pressed),
+                    None => self.c8.update_keys(key, pressed),
+                }
+            }
+            Input::Recording { ref mut recorder, .. } => recorder.key(&mut self.c8, key, pressed),
+            // the movie is in control
+            Input::Playing(_) => {}
+        }
+    }
+
     fn handle_keys(&mut self, key: &Button, pressed: bool) {
-        use piston::input::Button::Keyboard;
+        use piston::input::Button::{Controller, Keyboard};
         match *key {
-            Keyboard(Key::D1) => self.c8.update_keys(1, pressed),
-            Keyboard(Key::D2) => self.c8.update_keys(2, pressed),
-            Keyboard(Key::D3) => self.c8.update_keys(3, pressed),
-            Keyboard(Key::D4) => self.c8.update_keys(0xC, pressed),
-            Keyboard(Key::Q) => self.c8.update_keys(4, pressed),
-            Keyboard(Key::W) => self.c8.update_keys(5, pressed),
-            Keyboard(Key::E) => self.c8.update_keys(6, pressed),
-            Keyboard(Key::R) => self.c8.update_keys(0xD, pressed),
-            Keyboard(Key::A) => self.c8.update_keys(7, pressed),
-            Keyboard(Key::S) => self.c8.update_keys(8, pressed),
-            Keyboard(Key::D) => self.c8.update_keys(9, pressed),
-            Keyboard(Key::F) => self.c8.update_keys(0xE, pressed),
-            Keyboard(Key::Z) => self.c8.update_keys(0xA, pressed),
-            Keyboard(Key::X) => self.c8.update_keys(0, pressed),
-            Keyboard(Key::C) => self.c8.update_keys(0xB, pressed),
-            Keyboard(Key::V) => self.c8.update_keys(0xF, pressed),
+            // F1-F4 save to slots 1-4, F5-F8 load them back
+            Keyboard(Key::F1) if pressed => self.quick_save(1),
+            Keyboard(Key::F2) if pressed => self.quick_save(2),
+            Keyboard(Key::F3) if pressed => self.quick_save(3),
+            Keyboard(Key::F4) if pressed => self.quick_save(4),
+            Keyboard(Key::F5) if pressed => self.quick_load(1),
+            Keyboard(Key::F6) if pressed => self.quick_load(2),
+            Keyboard(Key::F7) if pressed => self.quick_load(3),
+            Keyboard(Key::F8) if pressed => self.quick_load(4),
+            Keyboard(Key::P) if pressed => {
+                self.paused = !self.paused;
+                self.c8.draw_flag = true;
+            }
+            Keyboard(Key::Period) if pressed => self.advance = self.paused,
+            Keyboard(Key::F10) if pressed => self.reset(),
+            Keyboard(Key::Tab) => {
+                self.fast_forwarding = pressed;
+                self.c8.draw_flag = true;
+            }
+            Keyboard(Key::Backquote) if pressed => {
+                self.slow_motion = !self.slow_motion;
+                self.c8.draw_flag = true;
+            }
+            // hold backspace to play the game backwards, except in movies which only go forwards
+            Keyboard(Key::Backspace) => {
+                if self.is_live() {
+                    self.rewinding = pressed;
+                }
+            }
+            Keyboard(key) => {
+                if let Some(hex) = self.keymap.hex_key(key) {
+                    self.keypad(hex, pressed);
+                }
+            }
+            Controller(button) => {
+                if let Some(hex) = self.keymap.hex_button(button.button) {
+                    self.keypad(hex, pressed);
+                }
+            }
             _ => {}
         }
     }
@@ -0,0 +1,61 @@
+use std::time::Duration;
+
+use rodio::Source;
+
+const SAMPLE_RATE: u32 = 44100;
+
+/// Loops an XO-CHIP 128 bit audio pattern. Each set bit is a high sample, each clear bit a low
+/// one, played back at 4000 * 2^((pitch - 64) / 48) bits per second.
+pub struct PatternWave {
+    pattern: [u8; 16],
+    bits_per_sample: f32,
+    position: f32,
+}
+
+impl PatternWave {
+    pub fn new(pattern: [u8; 16], pitch: u8) -> PatternWave {
+        let rate = 4000.0 * 2f32.powf((pitch as f32 - 64.0) / 48.0);
+        PatternWave {
+            pattern: pattern,
+            bits_per_sample: rate / SAMPLE_RATE as f32,
+            position: 0.0,
+        }
+    }
+}
+
+impl Iterator for PatternWave {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        let bit = self.position as usize;
+        let high = self.pattern[bit / 8] & (0x80 >> (bit % 8)) != 0;
+        self.position = (self.position + self.bits_per_sample) % 128.0;
+        Some(if high {
+            0.25
+        } else {
+            -0.25
+        })
+    }
+}
+
+impl Source for PatternWave {
+    #[inline]
+    fn get_current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    #[inline]
+    fn get_channels(&self) -> u16 {
+        1
+    }
+
+    #[inline]
+    fn get_samples_rate(&self) -> u32 {
+        SAMPLE_RATE
+    }
+
+    #[inline]
+    fn get_total_duration(&self) -> Option<Duration> {
+        None
+    }
+}
@@ -0,0 +1,71 @@
+use graphics::*;
+use opengl_graphics::{GlGraphics, Texture, TextureSettings, Filter};
+use piston::input::RenderArgs;
+
+use super::{Display, Indicator, RGBA, Shape, indicator_shapes};
+
+/// Draws with OpenGL 3.2, one alpha mask texture per colour.
+pub struct GlDisplay {
+    gl: GlGraphics,
+}
+
+impl GlDisplay {
+    pub fn new(gl: GlGraphics) -> GlDisplay {
+        GlDisplay { gl: gl }
+    }
+}
+
+impl Display for GlDisplay {
+    fn draw(&mut self,
+            gfx: &[u8],
+            intensity: Option<&[u8]>,
+            dimens: (usize, usize),
+            palette: &[RGBA; 4],
+            indicator: Indicator,
+            args: &RenderArgs) {
+        let (memwidth, memheight) = dimens;
+        let wscale = args.width as f64 / memwidth as f64;
+        let hscale = args.height as f64 / memheight as f64;
+        let palette = *palette;
+        let mut tsettings = TextureSettings::new();
+        tsettings.set_min(Filter::Nearest);
+        tsettings.set_mag(Filter::Nearest);
+        // one alpha mask per colour index, so both bitplanes and their overlap get a colour
+        let textures: Vec<(usize, Texture)> =
+            (1..4)
+                .filter(|&i| gfx.iter().any(|&p| p as usize == i))
+                .map(|i| {
+                    let mask: Vec<u8> = (0..memwidth * memheight)
+                                            .map(|p| if gfx[p] as usize != i {
+                                                0
+                                            } else {
+                                                intensity.map(|levels| levels[p]).unwrap_or(255)
+                                            })
+                                            .collect();
+                    (i,
+                     Texture::from_memory_alpha(&mask,
+                                                memwidth as u32,
+                                                memheight as u32,
+                                                &tsettings)
+                         .unwrap())
+                })
+                .collect();
+
+        let shapes = indicator_shapes(indicator, args.width as f64, args.height as f64);
+        self.gl.draw(args.viewport(), |c, gl| {
+            clear(palette[0], gl);
+            for &(i, ref texture) in &textures {
+                Image::new_color(palette[i]).draw(texture,
+                                                  default_draw_state(),
+                                                  c.transform.scale(wscale, hscale),
+                                                  gl);
+            }
+            for shape in &shapes {
+                match *shape {
+                    Shape::Rectangle(rect) => rectangle(palette[1], rect, c.transform, gl),
+                    Shape::Triangle(ref points) => polygon(palette[1], points, c.transform, gl),
+                }
+            }
+        });
+    }
+}
@@ -0,0 +1,58 @@
+// Drawing the screen. App hands a display the framebuffer, one palette index per pixel, and the
+// display scales it to the window however it likes.
+
+use piston::input::RenderArgs;
+
+mod gl;
+mod software;
+
+pub use self::gl::GlDisplay;
+pub use self::software::SoftwareDisplay;
+
+pub type RGBA = [f32; 4];
+
+/// Mode shown in the top right corner of the window.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Indicator {
+    None,
+    Paused,
+    FastForward,
+    SlowMotion,
+}
+
+pub trait Display {
+    /// Draws `gfx`, `dimens.0` pixels to a row and `dimens.1` rows, stretched over the window
+    /// with `indicator` on top. Each pixel is an index into `palette`, whose first colour is the
+    /// background. With `intensity`, each pixel's colour is mixed with the background by its
+    /// intensity from 0 to 255.
+    fn draw(&mut self,
+            gfx: &[u8],
+            intensity: Option<&[u8]>,
+            dimens: (usize, usize),
+            palette: &[RGBA; 4],
+            indicator: Indicator,
+            args: &RenderArgs);
+}
+
+// Indicator outlines in window coordinates
+enum Shape {
+    // x, y, width, height
+    Rectangle([f64; 4]),
+    Triangle([[f64; 2]; 3]),
+}
+
+// Pause bars, two play triangles for fast-forward or one for slow motion
+fn indicator_shapes(indicator: Indicator, width: f64, height: f64) -> Vec<Shape> {
+    let size = (height / 16.0).max(8.0);
+    let (x, y) = (width - size * 3.0, size);
+    let triangle = |x: f64| Shape::Triangle([[x, y], [x + size, y + size / 2.0], [x, y + size]]);
+    match indicator {
+        Indicator::None => vec![],
+        Indicator::Paused => {
+            vec![Shape::Rectangle([x, y, size / 3.0, size]),
+                 Shape::Rectangle([x + size * 2.0 / 3.0, y, size / 3.0, size])]
+        }
+        Indicator::FastForward => vec![triangle(x), triangle(x + size)],
+        Indicator::SlowMotion => vec![triangle(x)],
+    }
+}
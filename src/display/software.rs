@@ -0,0 +1,208 @@
+use std::mem;
+
+use graphics::*;
+use image::RgbaImage;
+use opengl_graphics::{GlGraphics, Texture, TextureSettings, Filter};
+use piston::input::RenderArgs;
+
+use super::{Display, Indicator, RGBA, Shape, indicator_shapes};
+
+/// Draws on the CPU into an RGBA buffer the size of the window, for machines without OpenGL
+/// 3.2 and for checking output pixel for pixel. With a window the finished frame is copied
+/// over as a single texture, which only needs OpenGL 2.1.
+pub struct SoftwareDisplay {
+    width: usize,
+    height: usize,
+    // 4 bytes per pixel, rows top to bottom
+    pixels: Vec<u8>,
+    gl: Option<GlGraphics>,
+    // the window's copy of the frame, replaced only when the window changes size
+    texture: Option<Texture>,
+}
+
+// Channels from 0 to 255, blended over `background` by alpha the way GL blends them
+fn to_u8(color: RGBA, background: [u8; 4]) -> [u8; 4] {
+    let mut out = [255; 4];
+    for i in 0..3 {
+        let channel = color[i] * color[3] + background[i] as f32 / 255.0 * (1.0 - color[3]);
+        out[i] = (channel * 255.0).round() as u8;
+    }
+    out
+}
+
+// Whether the point is inside the shape, triangles being the indicator's right-pointing kind
+fn contains(shape: &Shape, x: f64, y: f64) -> bool {
+    match *shape {
+        Shape::Rectangle([rx, ry, w, h]) => x >= rx && x < rx + w && y >= ry && y < ry + h,
+        Shape::Triangle([left, tip, _]) => {
+            let half = tip[1] - left[1];
+            let reach = (tip[0] - left[0]) * (1.0 - (y - tip[1]).abs() / half);
+            x >= left[0] && x < left[0] + reach
+        }
+    }
+}
+
+impl SoftwareDisplay {
+    /// A display that only draws into memory.
+    pub fn new() -> SoftwareDisplay {
+        SoftwareDisplay {
+            width: 0,
+            height: 0,
+            pixels: Vec::new(),
+            gl: None,
+            texture: None,
+        }
+    }
+
+    /// A display that shows each frame in the window `gl` draws to.
+    pub fn with_window(gl: GlGraphics) -> SoftwareDisplay {
+        SoftwareDisplay { gl: Some(gl), ..SoftwareDisplay::new() }
+    }
+
+    /// Draws a frame `width` by `height` pixels in size. Each pixel takes its colour from the
+    /// framebuffer pixel its top left corner falls in, so integer scales give even blocks.
+    pub fn render(&mut self,
+                  gfx: &[u8],
+                  intensity: Option<&[u8]>,
+                  dimens: (usize, usize),
+                  palette: &[RGBA; 4],
+                  indicator: Indicator,
+                  width: usize,
+                  height: usize) {
+        let (memwidth, memheight) = dimens;
+        let background = to_u8(palette[0], [0, 0, 0, 255]);
+        let colors = [background,
+                      to_u8(palette[1], background),
+                      to_u8(palette[2], background),
+                      to_u8(palette[3], background)];
+        let shapes = indicator_shapes(indicator, width as f64, height as f64);
+
+        self.width = width;
+        self.height = height;
+        self.pixels.clear();
+        for y in 0..height {
+            let row = (y * memheight / height) * memwidth;
+            for x in 0..width {
+                let (cx, cy) = (x as f64 + 0.5, y as f64 + 0.5);
+                let pixel = row + x * memwidth / width;
+                let color = if shapes.iter().any(|shape| contains(shape, cx, cy)) {
+                    colors[1]
+                } else {
+                    match intensity {
+                        Some(levels) if levels[pixel] < 255 => {
+                            let mut faded = palette[(gfx[pixel] & 3) as usize];
+                            faded[3] *= levels[pixel] as f32 / 255.0;
+                            to_u8(faded, background)
+                        }
+                        _ => colors[(gfx[pixel] & 3) as usize],
+                    }
+                };
+                self.pixels.extend_from_slice(&color);
+            }
+        }
+    }
+}
+
+impl Display for SoftwareDisplay {
+    fn draw(&mut self,
+            gfx: &[u8],
+            intensity: Option<&[u8]>,
+            dimens: (usize, usize),
+            palette: &[RGBA; 4],
+            indicator: Indicator,
+            args: &RenderArgs) {
+        self.render(gfx,
+                    intensity,
+                    dimens,
+                    palette,
+                    indicator,
+                    args.width as usize,
+                    args.height as usize);
+        if self.gl.is_none() {
+            return;
+        }
+        let size = (self.width as u32, self.height as u32);
+        // the image borrows the buffer for the upload and hands it back for the next frame
+        let pixels = mem::replace(&mut self.pixels, Vec::new());
+        let image = RgbaImage::from_raw(size.0, size.1, pixels).unwrap();
+        let same_size = match self.texture {
+            Some(ref texture) => texture.get_size() == size,
+            None => false,
+        };
+        if same_size {
+            self.texture.as_mut().unwrap().update(&image);
+        } else {
+            let mut tsettings = TextureSettings::new();
+            tsettings.set_min(Filter::Nearest);
+            tsettings.set_mag(Filter::Nearest);
+            self.texture = Some(Texture::from_image(&image, &tsettings));
+        }
+        {
+            let texture = self.texture.as_ref().unwrap();
+            self.gl.as_mut().unwrap().draw(args.viewport(), |c, gl| {
+                Image::new().draw(texture, default_draw_state(), c.transform, gl);
+            });
+        }
+        self.pixels = image.into_raw();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SoftwareDisplay;
+    use display::{Indicator, RGBA};
+
+    const PALETTE: [RGBA; 4] = [[0.0, 0.0, 0.0, 1.0],
+                                [1.0, 1.0, 1.0, 1.0],
+                                [1.0, 0.0, 0.0, 1.0],
+                                [0.0, 0.0, 1.0, 0.5]];
+
+    fn pixel(display: &SoftwareDisplay, x: usize, y: usize) -> &[u8] {
+        let i = (y * display.width + x) * 4;
+        &display.pixels[i..i + 4]
+    }
+
+    #[test]
+    fn render_framebuffer() {
+        let mut display = SoftwareDisplay::new();
+        // each framebuffer pixel becomes a 2x2 block
+        display.render(&[0, 1, 2, 3], None, (2, 2), &PALETTE, Indicator::None, 4, 4);
+        assert_eq!((display.width, display.height), (4, 4));
+        assert_eq!(display.pixels.len(), 4 * 4 * 4);
+        assert_eq!(pixel(&display, 1, 1), &[0, 0, 0, 255]);
+        assert_eq!(pixel(&display, 2, 0), &[255, 255, 255, 255]);
+        assert_eq!(pixel(&display, 3, 1), &[255, 255, 255, 255]);
+        assert_eq!(pixel(&display, 0, 2), &[255, 0, 0, 255]);
+        // half transparent blue over the black background
+        assert_eq!(pixel(&display, 3, 3), &[0, 0, 128, 255]);
+    }
+
+    #[test]
+    fn render_indicator() {
+        let mut display = SoftwareDisplay::new();
+        // the pause bars are 8 pixels high from (40, 8), each bar a third of that wide
+        display.render(&[0; 64 * 32], None, (64, 32), &PALETTE, Indicator::Paused, 64, 32);
+        assert_eq!(pixel(&display, 40, 8), &[255, 255, 255, 255]);
+        assert_eq!(pixel(&display, 42, 15), &[255, 255, 255, 255]);
+        assert_eq!(pixel(&display, 43, 8), &[0, 0, 0, 255]);
+        assert_eq!(pixel(&display, 45, 8), &[255, 255, 255, 255]);
+        assert_eq!(pixel(&display, 40, 16), &[0, 0, 0, 255]);
+        assert_eq!(pixel(&display, 39, 8), &[0, 0, 0, 255]);
+    }
+
+    #[test]
+    fn render_intensity() {
+        let mut display = SoftwareDisplay::new();
+        display.render(&[1, 1, 2, 0],
+                       Some(&[255, 128, 51, 0]),
+                       (4, 1),
+                       &PALETTE,
+                       Indicator::None,
+                       4,
+                       1);
+        assert_eq!(pixel(&display, 0, 0), &[255, 255, 255, 255]);
+        assert_eq!(pixel(&display, 1, 0), &[128, 128, 128, 255]);
+        assert_eq!(pixel(&display, 2, 0), &[51, 0, 0, 255]);
+        assert_eq!(pixel(&display, 3, 0), &[0, 0, 0, 255]);
+    }
+}
@@ -0,0 +1,90 @@
+// Local TCP server for gdb. Everything is non-blocking and polled from the update loop, so the
+// window keeps rendering while gdb has the program stopped.
+
+use std::io::{self, ErrorKind, Read, Write};
+use std::net::{TcpListener, TcpStream};
+
+use chip8_core::{Chip8, Chip8Error, StepOutcome};
+use chip8_core::gdb::GdbStub;
+
+pub struct GdbServer {
+    listener: TcpListener,
+    // only one gdb at a time, the program runs freely while none is attached
+    client: Option<(TcpStream, GdbStub)>,
+}
+
+impl GdbServer {
+    pub fn bind(port: u16) -> io::Result<GdbServer> {
+        let listener = try!(TcpListener::bind(("127.0.0.1", port)));
+        try!(listener.set_nonblocking(true));
+        println!("Waiting for gdb on 127.0.0.1:{}", port);
+        Ok(GdbServer {
+            listener: listener,
+            client: None,
+        })
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.client.as_ref().map(|&(_, ref stub)| stub.is_paused()).unwrap_or(false)
+    }
+
+    /// Accepts a new gdb and handles whatever the attached one has sent.
+    pub fn poll(&mut self, c8: &mut Chip8) {
+        if self.client.is_none() {
+            match self.listener.accept() {
+                Ok((stream, addr)) => {
+                    if stream.set_nonblocking(true).is_ok() {
+                        println!("gdb attached from {}", addr);
+                        self.client = Some((stream, GdbStub::new()));
+                    }
+                }
+                Err(ref e) if e.kind() == ErrorKind::WouldBlock => {}
+                Err(e) => println!("Could not accept gdb connection: {}", e),
+            }
+        }
+
+        let mut closed = false;
+        if let Some((ref mut stream, ref mut stub)) = self.client {
+            let mut buf = [0; 4096];
+            loop {
+                match stream.read(&mut buf) {
+                    Ok(0) => {
+                        closed = true;
+                        break;
+                    }
+                    Ok(n) => {
+                        let reply = stub.receive(c8, &buf[..n]);
+                        if stream.write_all(&reply).is_err() {
+                            closed = true;
+                            break;
+                        }
+                    }
+                    Err(ref e) if e.kind() == ErrorKind::WouldBlock => break,
+                    Err(_) => {
+                        closed = true;
+                        break;
+                    }
+                }
+            }
+        }
+        if closed {
+            println!("gdb detached");
+            self.client = None;
+        }
+    }
+
+    /// Steps through the stub while gdb is attached, and straight through the machine otherwise.
+    pub fn step(&mut self, c8: &mut Chip8) -> Result<Option<StepOutcome>, Chip8Error> {
+        match self.client {
+            Some((ref mut stream, ref mut stub)) => {
+                let (result, reply) = stub.step(c8);
+                if !reply.is_empty() {
+                    // a dead connection is noticed on the next poll
+                    let _ = stream.write_all(&reply);
+                }
+                result
+            }
+            None => c8.step().map(Some),
+        }
+    }
+}
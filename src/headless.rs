@@ -0,0 +1,210 @@
+// Runs a ROM without a window or audio device, for CI and batch jobs.
+
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+
+use png::{self, HasParameters};
+use rustc_serialize::json;
+
+use app::read_rom;
+use chip8_core::{Chip8, Chip8Error, Quirks, StepOutcome};
+use chip8_core::trace::Tracer;
+use chip8_core::movie::{Movie, MovieError, Player};
+
+pub struct Options {
+    pub frames: usize,
+    // instructions per 60Hz frame
+    pub ipf: usize,
+    pub quirks: Quirks,
+    pub seed: Option<u64>,
+    // write the final framebuffer as a PNG here, otherwise it is printed as text
+    pub png: Option<String>,
+    // write the registers as JSON here, otherwise they are printed
+    pub regs: Option<String>,
+    pub trace: Option<Tracer<BufWriter<File>>>,
+    // play this movie back, which then decides the quirks, seed, ipf and number of frames
+    pub movie: Option<Movie>,
+}
+
+/// Why the run stopped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Halt {
+    // ran for the requested number of frames
+    Frames,
+    // the program executed 0x00FD
+    Exited,
+    // the program jumped to itself, the usual way CHIP-8 programs stop
+    Loop,
+    Fault(Chip8Error),
+}
+
+#[derive(RustcEncodable)]
+struct RegisterDump {
+    frames: usize,
+    halt: String,
+    pc: u16,
+    i: u16,
+    sp: u16,
+    v: Vec<u8>,
+    stack: Vec<u16>,
+    delay_timer: u8,
+    sound_timer: u8,
+    extended_mode: bool,
+}
+
+pub struct Report {
+    pub halt: Halt,
+    // whether movie playback ended on the recorded screen
+    pub playback: Option<Result<(), MovieError>>,
+}
+
+/// Runs `c8` for up to `frames` 60Hz frames of `ipf` instructions and a timer tick each.
+/// Returns the number of frames run and why it stopped, or an error if the trace couldn't be
+/// written.
+pub fn run_frames<W: Write>(c8: &mut Chip8,
+                            ipf: usize,
+                            frames: usize,
+                            tracer: &mut Option<Tracer<W>>,
+                            player: &mut Option<Player>)
+                            -> io::Result<(usize, Halt)> {
+    for frame in 0..frames {
+        for _ in 0..ipf {
+            // a jump can only reach the first 4K, past that 0x1NNN | pc is another opcode
+            let pc = c8.pc();
+            if pc <= 0xFFF && c8.current_opcode() == Some(0x1000 | pc) {
+                return Ok((frame, Halt::Loop));
+            }
+            if let Some(ref mut player) = *player {
+                player.apply(c8);
+            }
+            let result = match *tracer {
+                Some(ref mut tracer) => try!(tracer.step(c8, |c8| c8.step().map(Some))),
+                None => c8.step().map(Some),
+            };
+            match result {
+                Ok(Some(StepOutcome::Executed)) |
+                Ok(Some(StepOutcome::WaitingForKey)) |
+                Ok(None) => {}
+                // the rest of the frame would only wait as well
+                Ok(Some(StepOutcome::WaitingForVblank)) => break,
+                Ok(Some(StepOutcome::Exited)) => return Ok((frame, Halt::Exited)),
+                Err(e) => return Ok((frame, Halt::Fault(e))),
+            }
+            if let Some(ref mut player) = *player {
+                player.stepped();
+            }
+        }
+        c8.tick();
+        if let Some(ref mut player) = *player {
+            player.ticked();
+        }
+        if let Some(ref mut tracer) = *tracer {
+            tracer.frame();
+        }
+    }
+    Ok((frames, Halt::Frames))
+}
+
+pub fn run(program_file: String, mut options: Options) -> io::Result<Report> {
+    let rom = try!(read_rom(&program_file).map_err(|e| {
+        io::Error::new(e.kind(), format!("could not read {}: {}", program_file, e))
+    }));
+    let mut c8 = Chip8::init();
+    c8.quirks = options.quirks;
+    if let Some(seed) = options.seed {
+        c8.seed(seed);
+    }
+    c8.load_rom(&rom);
+
+    let mut player = None;
+    if let Some(movie) = options.movie.take() {
+        let (ipf, frames) = (movie.steps_per_frame as usize, movie.frames as usize);
+        match Player::start(movie, &mut c8) {
+            Ok(p) => player = Some(p),
+            Err(e) => {
+                return Ok(Report {
+                    halt: Halt::Frames,
+                    playback: Some(Err(e)),
+                })
+            }
+        }
+        options.ipf = ipf;
+        options.frames = frames;
+    }
+
+    let (frames, halt) = try!(run_frames(&mut c8,
+                                         options.ipf,
+                                         options.frames,
+                                         &mut options.trace,
+                                         &mut player));
+    if let Some(tracer) = options.trace.take() {
+        try!(tracer.into_inner());
+    }
+
+    match options.png {
+        Some(ref path) => try!(write_png(&c8, path)),
+        None => print!("{}", framebuffer_text(&c8)),
+    }
+
+    let dump = RegisterDump {
+        frames: frames,
+        halt: match halt {
+            Halt::Frames => String::from("frames"),
+            Halt::Exited => String::from("exited"),
+            Halt::Loop => String::from("loop"),
+            Halt::Fault(e) => format!("{}", e),
+        },
+        pc: c8.pc(),
+        i: c8.i(),
+        sp: c8.sp(),
+        v: c8.v().to_vec(),
+        stack: c8.stack().to_vec(),
+        delay_timer: c8.delay_timer(),
+        sound_timer: c8.sound_timer,
+        extended_mode: c8.extended_mode,
+    };
+    let dump = format!("{}\n", json::as_pretty_json(&dump));
+    match options.regs {
+        Some(ref path) => try!(File::create(path).and_then(|mut f| f.write_all(dump.as_bytes()))),
+        None => print!("{}", dump),
+    }
+    Ok(Report {
+        halt: halt,
+        playback: player.map(|p| p.verify(&c8)),
+    })
+}
+
+/// One line per row, `.` for a blank pixel, `#` for plane 1 and the colour index for the XO-CHIP
+/// second plane and overlap.
+pub fn framebuffer_text(c8: &Chip8) -> String {
+    let (width, height) = c8.screen_dimens();
+    let mut out = String::with_capacity((width + 1) * height);
+    for y in 0..height {
+        for x in 0..width {
+            out.push(match c8.gfx[x + y * width] {
+                0 => '.',
+                1 => '#',
+                2 => '2',
+                _ => '3',
+            });
+        }
+        out.push('\n');
+    }
+    out
+}
+
+fn write_png(c8: &Chip8, path: &str) -> io::Result<()> {
+    let palette = [[0, 0, 0], [255, 255, 255], [255, 102, 0], [102, 34, 0]];
+    let (width, height) = c8.screen_dimens();
+    let mut data = Vec::with_capacity(width * height * 3);
+    for &p in &c8.gfx[..width * height] {
+        data.extend_from_slice(&palette[(p & 3) as usize]);
+    }
+
+    let file = try!(File::create(path));
+    let mut encoder = png::Encoder::new(file, width as u32, height as u32);
+    encoder.set(png::ColorType::RGB).set(png::BitDepth::Eight);
+    let mut writer = try!(encoder.write_header());
+    try!(writer.write_image_data(&data));
+    Ok(())
+}
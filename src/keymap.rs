@@ -0,0 +1,322 @@
+// Keyboard and gamepad to hex keypad mapping, loaded from a config file like:
+//
+//   # hex key = keyboard keys as named by piston, gamepad buttons and stick directions
+//   5 = W Up Button0
+//   8 = S Down Axis1+
+//   threshold = 0.6
+//
+//   [brix.ch8]
+//   4 = Left Axis0-
+//   6 = Right Axis0+
+//
+// Lines before any section apply to every ROM, a section named after a ROM's file name, with or
+// without its extension, applies on top for that ROM only. Hex keys that aren't mentioned keep the
+// default layout, and a key, button or stick direction belongs to the hex key it was assigned to
+// last. `threshold` is how far a stick has to move off centre, from 0 to 1, to press a key.
+// The keys the window uses itself, such as P to pause and the function keys, can't be bound.
+
+use std::fmt;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+use piston::input::Key;
+
+// Gamepad bindings for every ROM and a few games that need something else, applied before the
+// config file. Button and axis numbers are SDL's, which for most pads put the left stick on axes
+// 0 and 1 and the face buttons first.
+const DEFAULTS: &'static str = "
+4 = Q Axis0-
+6 = E Axis0+
+2 = D2 Axis1-
+8 = S Axis1+
+5 = W Button0
+
+# 4 rotates, 5 and 6 move, 7 drops
+[tetris]
+4 = Q Button0
+5 = W Axis0-
+6 = E Axis0+
+7 = A Axis1+
+
+# 4 and 6 move the paddle
+[brix]
+4 = Q Axis0-
+6 = E Axis0+
+
+# 4 and 6 walk, E jumps
+[ant]
+4 = Q Axis0-
+6 = E Axis0+
+E = F Button0 Axis1-
+";
+
+// Handled by the window before the keymap is looked at, so bindings to them would never fire
+const RESERVED: [Key; 16] = [Key::Escape, Key::Backspace, Key::Tab, Key::P, Key::Period,
+                             Key::Backquote, Key::F1, Key::F2, Key::F3, Key::F4, Key::F5,
+                             Key::F6, Key::F7, Key::F8, Key::F9, Key::F10];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Binding {
+    Key(Key),
+    Button(u8),
+    // an axis and whether it's the positive direction
+    Axis(u8, bool),
+}
+
+pub struct Keymap {
+    // bindings for each hex key
+    keys: Vec<Vec<Binding>>,
+    // stick position past which an axis binding is pressed
+    threshold: f64,
+}
+
+impl Default for Keymap {
+    fn default() -> Keymap {
+        let layout = [(0x1, Key::D1), (0x2, Key::D2), (0x3, Key::D3), (0xC, Key::D4),
+                      (0x4, Key::Q), (0x5, Key::W), (0x6, Key::E), (0xD, Key::R),
+                      (0x7, Key::A), (0x8, Key::S), (0x9, Key::D), (0xE, Key::F),
+                      (0xA, Key::Z), (0x0, Key::X), (0xB, Key::C), (0xF, Key::V)];
+        let mut keymap = Keymap {
+            keys: vec![Vec::new(); 16],
+            threshold: 0.5,
+        };
+        for &(hex, key) in layout.iter() {
+            keymap.keys[hex].push(Binding::Key(key));
+        }
+        keymap
+    }
+}
+
+// Piston has no names for its keys beyond Debug, so search the ranges its key codes live in:
+// ASCII for printable keys and 0x40000000 upwards for the rest
+fn key_from_name(name: &str) -> Option<Key> {
+    let name = name.to_lowercase();
+    (0..0x80u32)
+        .chain(0x40000039..0x40000120)
+        .map(Key::from)
+        .find(|&key| key != Key::Unknown && format!("{:?}", key).to_lowercase() == name)
+}
+
+// ButtonN, AxisN+ or AxisN-, otherwise a keyboard key
+fn binding_from_name(name: &str) -> Option<Binding> {
+    let lower = name.to_lowercase();
+    if lower.starts_with("button") {
+        if let Ok(button) = lower["button".len()..].parse() {
+            return Some(Binding::Button(button));
+        }
+    } else if lower.starts_with("axis") && (lower.ends_with('+') || lower.ends_with('-')) {
+        if let Ok(axis) = lower["axis".len()..lower.len() - 1].parse() {
+            return Some(Binding::Axis(axis, lower.ends_with('+')));
+        }
+    }
+    key_from_name(name).map(Binding::Key)
+}
+
+impl fmt::Display for Binding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Binding::Key(key) => write!(f, "{:?}", key),
+            Binding::Button(button) => write!(f, "Button{}", button),
+            Binding::Axis(axis, positive) => {
+                write!(f, "Axis{}{}", axis, if positive {
+                    '+'
+                } else {
+                    '-'
+                })
+            }
+        }
+    }
+}
+
+// Sections match the ROM's file name, or its name without the extension in any case
+fn section_matches(section: &str, rom: &str) -> bool {
+    let stem = Path::new(rom).file_stem().map(|s| s.to_string_lossy().to_lowercase());
+    section == rom || stem == Some(section.to_lowercase())
+}
+
+impl Keymap {
+    /// The default layout with the built in gamepad bindings for `rom`.
+    pub fn for_rom(rom: &str) -> Keymap {
+        let mut keymap = Keymap::default();
+        keymap.apply_text(DEFAULTS, rom).expect("invalid default keymap");
+        keymap
+    }
+
+    /// Loads a config file over the defaults, applying the section for `rom` if there is one.
+    pub fn load(path: &Path, rom: &str) -> Result<Keymap, String> {
+        let mut text = String::new();
+        try!(File::open(path)
+                 .and_then(|mut f| f.read_to_string(&mut text))
+                 .map_err(|e| format!("could not read {}: {}", path.display(), e)));
+
+        let mut keymap = Keymap::for_rom(rom);
+        try!(keymap.apply_text(&text, rom)
+                   .map_err(|(line, e)| format!("{}:{}: {}", path.display(), line, e)));
+        Ok(keymap)
+    }
+
+    // Applies the lines of a config, failing with the line number and message
+    fn apply_text(&mut self, text: &str, rom: &str) -> Result<(), (usize, String)> {
+        let mut section_applies = true;
+        for (n, line) in text.lines().enumerate() {
+            let line = match line.find('#') {
+                Some(i) => line[..i].trim(),
+                None => line.trim(),
+            };
+            if line.is_empty() {
+                continue;
+            }
+            if line.starts_with('[') && line.ends_with(']') {
+                section_applies = section_matches(&line[1..line.len() - 1], rom);
+                continue;
+            }
+            if !section_applies {
+                continue;
+            }
+            try!(self.apply(line).map_err(|e| (n + 1, e)));
+        }
+        Ok(())
+    }
+
+    // <hex key> = <binding> <binding> ..., or threshold = <position>
+    fn apply(&mut self, line: &str) -> Result<(), String> {
+        let mut parts = line.splitn(2, '=');
+        let name = parts.next().unwrap().trim();
+        let value = try!(parts.next().ok_or(String::from("expected <hex key> = <keys>")));
+
+        if name == "threshold" {
+            self.threshold = match value.trim().parse() {
+                Ok(threshold) if threshold > 0.0 && threshold < 1.0 => threshold,
+                _ => return Err(format!("{} is not a threshold between 0 and 1", value.trim())),
+            };
+            return Ok(());
+        }
+        let hex = match u8::from_str_radix(name, 16) {
+            Ok(hex) if hex < 16 => hex as usize,
+            _ => return Err(format!("{} is not a hex key", name)),
+        };
+
+        let mut bindings = Vec::new();
+        for name in value.split_whitespace() {
+            let binding = try!(binding_from_name(name).ok_or(format!("unknown key {}", name)));
+            if let Binding::Key(key) = binding {
+                if RESERVED.contains(&key) {
+                    return Err(format!("{} is reserved", name));
+                }
+            }
+            bindings.push(binding);
+        }
+        for other in self.keys.iter_mut() {
+            other.retain(|b| !bindings.contains(b));
+        }
+        self.keys[hex] = bindings;
+        Ok(())
+    }
+
+    fn hex(&self, binding: Binding) -> Option<u8> {
+        self.keys.iter().position(|bindings| bindings.contains(&binding)).map(|hex| hex as u8)
+    }
+
+    /// The hex key a keyboard key is mapped to.
+    pub fn hex_key(&self, key: Key) -> Option<u8> {
+        self.hex(Binding::Key(key))
+    }
+
+    /// The hex key a gamepad button is mapped to.
+    pub fn hex_button(&self, button: u8) -> Option<u8> {
+        self.hex(Binding::Button(button))
+    }
+
+    /// The hex key pressed by a stick at `position`, from -1 to 1, if it's past the threshold.
+    pub fn hex_axis(&self, axis: u8, position: f64) -> Option<u8> {
+        if position >= self.threshold {
+            self.hex(Binding::Axis(axis, true))
+        } else if position <= -self.threshold {
+            self.hex(Binding::Axis(axis, false))
+        } else {
+            None
+        }
+    }
+}
+
+impl fmt::Display for Keymap {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (hex, bindings) in self.keys.iter().enumerate() {
+            let names: Vec<String> = bindings.iter().map(|b| format!("{}", b)).collect();
+            try!(writeln!(f, "{:X} = {}", hex, names.join(" ")));
+        }
+        writeln!(f, "threshold = {}", self.threshold)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sections() {
+        assert!(section_matches("brix", "brix.ch8"));
+        assert!(section_matches("brix.ch8", "brix.ch8"));
+        assert!(section_matches("BRIX", "Brix.ch8"));
+        assert!(!section_matches("brix", "breakout.ch8"));
+        assert!(!section_matches("brix.c8", "brix.ch8"));
+
+        let text = "5 = W\n[brix]\n5 = Z\n[pong.ch8]\n5 = X\n";
+        let mut keymap = Keymap::default();
+        keymap.apply_text(text, "brix.ch8").unwrap();
+        assert_eq!(keymap.hex_key(Key::Z), Some(5));
+        assert_eq!(keymap.hex_key(Key::X), Some(0));
+        let mut keymap = Keymap::default();
+        keymap.apply_text(text, "pong.ch8").unwrap();
+        assert_eq!(keymap.hex_key(Key::X), Some(5));
+        assert_eq!(keymap.hex_key(Key::Z), Some(0xA));
+    }
+
+    #[test]
+    fn rebinding() {
+        let mut keymap = Keymap::default();
+        keymap.apply_text("5 = Q Up\n", "rom.ch8").unwrap();
+        assert_eq!(keymap.hex_key(Key::Q), Some(5));
+        assert_eq!(keymap.hex_key(Key::Up), Some(5));
+        // Q left 4 and W went with the rest of 5's old bindings
+        assert!(keymap.keys[4].is_empty());
+        assert_eq!(keymap.hex_key(Key::W), None);
+
+        keymap.apply_text("6 = Up Button0\n7 = Button0", "rom.ch8").unwrap();
+        assert_eq!(keymap.hex_key(Key::Up), Some(6));
+        assert_eq!(keymap.hex_button(0), Some(7));
+        assert_eq!(keymap.keys[6], vec![Binding::Key(Key::Up)]);
+    }
+
+    #[test]
+    fn threshold() {
+        let mut keymap = Keymap::default();
+        keymap.apply_text("8 = Axis1+\nthreshold = 0.3", "rom.ch8").unwrap();
+        assert_eq!(keymap.hex_axis(1, 0.3), Some(8));
+        assert_eq!(keymap.hex_axis(1, 0.29), None);
+        assert_eq!(keymap.hex_axis(1, -1.0), None);
+        for bad in &["0", "1", "-0.5", "half"] {
+            let line = format!("threshold = {}", bad);
+            assert_eq!(keymap.apply(&line),
+                       Err(format!("{} is not a threshold between 0 and 1", bad)));
+        }
+        assert_eq!(keymap.threshold, 0.3);
+    }
+
+    #[test]
+    fn errors() {
+        let mut keymap = Keymap::default();
+        let text = "# comment\n\n5 = W\n[other]\nnonsense\n[rom]\n5 = W\nG = Q\n";
+        assert_eq!(keymap.apply_text(text, "rom.ch8"),
+                   Err((8, String::from("G is not a hex key"))));
+        assert_eq!(keymap.apply_text("\n5 = Nope", "rom.ch8"),
+                   Err((2, String::from("unknown key Nope"))));
+        assert_eq!(keymap.apply_text("5 W", "rom.ch8"),
+                   Err((1, String::from("expected <hex key> = <keys>"))));
+        assert_eq!(keymap.apply_text("1 = D1\n5 = W Tab", "rom.ch8"),
+                   Err((2, String::from("Tab is reserved"))));
+        assert_eq!(keymap.apply_text("5 = F10", "rom.ch8"),
+                   Err((1, String::from("F10 is reserved"))));
+    }
+}
@@ -1,4 +1,3 @@
-#![feature(step_by)]
 #![feature(plugin)]
 #![feature(const_fn)]
 #![plugin(docopt_macros)]
@@ -13,6 +12,10 @@ extern crate read_color;
 extern crate rustc_serialize;
 extern crate docopt;
 extern crate rodio;
+extern crate png;
+extern crate image;
+extern crate libc;
+extern crate chip8_core;
 
 use piston::window::WindowSettings;
 use piston::input::*;
@@ -20,26 +23,276 @@ use piston::event_loop::*;
 use sdl2_window::Sdl2Window;
 use opengl_graphics::{GlGraphics, OpenGL};
 
-mod chip8;
+use std::fs::File;
+use std::io::{BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+
+use chip8_core::{Quirks, VIP_MAX_IPF, asm, disasm};
+use chip8_core::trace::Tracer;
+use chip8_core::movie::Movie;
+use chip8_core::{Rng, XorShift};
+
 mod app;
+mod audio;
+mod display;
+mod rewind;
+mod phosphor;
+mod headless;
+mod gdbserver;
+mod keymap;
+mod tty;
+mod watch;
 
 docopt!(Args derive Debug, "
 Chip8.
 
 Usage:
-	chip8 <filename> [--speed=<hz>] [(--foreground=<color> --background=<color>)] [--no-overdraw]
+	chip8 <filename> [--ipf=<n>] [--display-wait] [--timing=<model>] [(--foreground=<color> --background=<color>)] [--no-overdraw] [--quirks=<profile>] [--plane2=<color>] [--overlap=<color>] [--seed=<n>] [(--record=<file> | --play=<file> | --debug | --gdb=<port>)] [--keymap=<file>] [--print-keymap] [--fast-forward=<n>] [--watch [--restart-pc=<addr>]] [--trace=<file>] [--trace-pc=<ranges>] [--trace-frames=<range>] [--vip=<file>] [--renderer=<name>] [--persistence=<frames>]
+	chip8 headless <filename> [--frames=<n>] [--ipf=<n>] [--display-wait] [--timing=<model>] [--quirks=<profile>] [--seed=<n>] [--play=<file>] [--png=<file>] [--regs=<file>] [--trace=<file>] [--trace-pc=<ranges>] [--trace-frames=<range>]
+	chip8 tty <filename> [--ipf=<n>] [--display-wait] [--timing=<model>] [--quirks=<profile>] [--seed=<n>] [--keymap=<file>] [(--foreground=<color> --background=<color>)] [--plane2=<color>] [--overlap=<color>] [--braille]
+	chip8 disasm <filename>
+	chip8 asm <filename> [--output=<file>]
 
 Options:
-    --speed=<hz>           Set the emulation clock speed [default: 240].
+    --ipf=<n>              Instructions to run per 60Hz frame [default: 4].
+    --display-wait         Make sprite drawing wait for the next frame, like the COSMAC VIP.
+    --timing=<model>       ipf to run --ipf instructions per frame, or vip to charge each one
+                           what it cost on the COSMAC VIP, with display wait [default: ipf].
 	--foreground=<color>   Set the foreground color in hex [default: FFFFFF]
 	--background=<color>   Set the background color in hex [default: 000000]
+    --plane2=<color>       Set the XO-CHIP second bitplane color in hex [default: FF6600]
+    --overlap=<color>      Set the XO-CHIP color where both bitplanes are set in hex [default: 662200]
     --no-overdraw          Force a redraw for all DYXN instructions. 
-", flag_speed: i32);
+    --persistence=<frames> Fade pixels out over this many frames to hide flicker, 0 for off
+                           [default: 0].
+    --renderer=<name>      gl to draw with OpenGL 3.2, or software to draw on the CPU and only
+                           need OpenGL 2.1 [default: gl].
+    --seed=<n>             Seed the random number generator so runs can be repeated.
+    --record=<file>        Record keypad input to a movie file, written on exit.
+    --play=<file>          Play back a movie, checking it ends on the recorded screen.
+    --keymap=<file>        Keyboard and gamepad mapping, defaults to ~/.config/chip8/keymap.
+    --print-keymap         Print the keyboard and gamepad mapping for the ROM and exit.
+    --fast-forward=<n>     Speed while Tab is held, as a multiple of normal speed [default: 4].
+    --watch                Reload the ROM whenever the file changes.
+    --restart-pc=<addr>    Watch: reload into the state from when execution first reached addr.
+    --vip=<file>           Run the ROM on an emulated COSMAC VIP under this interpreter image.
+    --debug                Start paused with a debugger reading commands from stdin.
+    --gdb=<port>           Listen on a local port for gdb to attach with target remote.
+    --quirks=<profile>     Interpreter behaviour to emulate, one of chip8, chip48, schip10, schip11
+                           or xochip [default: schip11].
+    --frames=<n>           Headless: number of 60Hz frames to run for [default: 600].
+    --png=<file>           Headless: write the final screen to a PNG instead of printing it.
+    --regs=<file>          Headless: write the final registers as JSON instead of printing them.
+    --trace=<file>         Write a line per executed instruction to a file.
+    --trace-pc=<ranges>    Trace: only instructions in these address ranges, e.g. 0x200-0x2FF,0x300.
+    --trace-frames=<range> Trace: only instructions run in these 60Hz frames, e.g. 60-120.
+    --braille              Tty: draw with braille dots instead of half blocks, in one colour.
+    --output=<file>        Asm: where to write the ROM, defaults to the source name with .ch8.
+", flag_ipf: usize, flag_fast_forward: f64, flag_persistence: u32);
 
 fn main() {
     let args: Args = Args::docopt().decode().unwrap_or_else(|e| e.exit());
 
-    let opengl = OpenGL::V3_2;
+    if args.cmd_disasm {
+        let mut rom = Vec::new();
+        if let Err(e) = File::open(&args.arg_filename).and_then(|mut f| f.read_to_end(&mut rom)) {
+            println!("Could not read {}: {}", args.arg_filename, e);
+            std::process::exit(1);
+        }
+        print!("{}", disasm::disassemble(&rom));
+        return;
+    }
+    if args.cmd_asm {
+        let rom = asm::assemble_file(&args.arg_filename).unwrap_or_else(|e| {
+            println!("{}", e);
+            std::process::exit(1);
+        });
+        let output = if args.flag_output.is_empty() {
+            Path::new(&args.arg_filename).with_extension("ch8")
+        } else {
+            Path::new(&args.flag_output).to_path_buf()
+        };
+        if let Err(e) = File::create(&output).and_then(|mut f| f.write_all(&rom)) {
+            println!("Could not write {}: {}", output.display(), e);
+            std::process::exit(1);
+        }
+        return;
+    }
+    let mut quirks = Quirks::from_name(&args.flag_quirks).unwrap_or_else(|| {
+        println!("Unknown quirks profile {}", args.flag_quirks);
+        std::process::exit(1);
+    });
+    quirks.display_wait = args.flag_display_wait;
+    if args.flag_ipf == 0 {
+        println!("--ipf must be at least 1");
+        std::process::exit(1);
+    }
+    // with VIP timing the cycle budget ends each frame, not the instruction count
+    let ipf = match &*args.flag_timing {
+        "ipf" => args.flag_ipf,
+        "vip" => {
+            quirks.vip_timing = true;
+            quirks.display_wait = true;
+            VIP_MAX_IPF
+        }
+        _ => {
+            println!("Unknown timing model {}", args.flag_timing);
+            std::process::exit(1);
+        }
+    };
+    let seed = if args.flag_seed.is_empty() {
+        None
+    } else {
+        Some(args.flag_seed.parse().unwrap_or_else(|_| {
+            println!("Invalid seed {}", args.flag_seed);
+            std::process::exit(1);
+        }))
+    };
+    let tracer = make_tracer(&args).unwrap_or_else(|e| {
+        println!("{}", e);
+        std::process::exit(1);
+    });
+
+    let movie = if args.flag_play.is_empty() {
+        None
+    } else {
+        let mut text = String::new();
+        if let Err(e) = File::open(&args.flag_play).and_then(|mut f| f.read_to_string(&mut text)) {
+            println!("Could not read {}: {}", args.flag_play, e);
+            std::process::exit(1);
+        }
+        Some(Movie::parse(&text).unwrap_or_else(|e| {
+            println!("Invalid movie {}: {}", args.flag_play, e);
+            std::process::exit(1);
+        }))
+    };
+
+    if args.cmd_headless {
+        let options = headless::Options {
+            frames: args.flag_frames.parse().unwrap_or_else(|_| {
+                println!("Invalid frame count {}", args.flag_frames);
+                std::process::exit(1);
+            }),
+            ipf: ipf,
+            quirks: quirks,
+            seed: seed,
+            png: if args.flag_png.is_empty() {
+                None
+            } else {
+                Some(args.flag_png.clone())
+            },
+            regs: if args.flag_regs.is_empty() {
+                None
+            } else {
+                Some(args.flag_regs.clone())
+            },
+            trace: tracer,
+            movie: movie,
+        };
+        match headless::run(args.arg_filename.clone(), options) {
+            Ok(report) => {
+                match report.playback {
+                    Some(Ok(())) => println!("Playback matches the recording"),
+                    Some(Err(e)) => {
+                        println!("{}", e);
+                        std::process::exit(3);
+                    }
+                    None => {}
+                }
+                if let headless::Halt::Fault(e) = report.halt {
+                    println!("Emulation halted: {}", e);
+                    std::process::exit(2);
+                }
+                std::process::exit(0);
+            }
+            Err(e) => {
+                println!("Headless run failed: {}", e);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    let keymap = load_keymap(&args).unwrap_or_else(|e| {
+        println!("Invalid keymap: {}", e);
+        std::process::exit(1);
+    });
+    if args.flag_print_keymap {
+        print!("{}", keymap);
+        return;
+    }
+    let palette = [parse_color(&args.flag_background, [0, 0, 0, 255]),
+                   parse_color(&args.flag_foreground, [255, 255, 255, 255]),
+                   parse_color(&args.flag_plane2, [255, 102, 0, 255]),
+                   parse_color(&args.flag_overlap, [102, 34, 0, 255])];
+
+    if args.cmd_tty {
+        let options = tty::Options {
+            ipf: ipf,
+            quirks: quirks,
+            seed: seed,
+            keymap: keymap,
+            palette: palette,
+            braille: args.flag_braille,
+        };
+        if let Err(e) = tty::run(args.arg_filename.clone(), options) {
+            println!("Could not run in the terminal: {}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    let gdb = if args.flag_gdb.is_empty() {
+        None
+    } else {
+        let server = args.flag_gdb
+                         .parse()
+                         .map_err(|_| format!("Invalid port {}", args.flag_gdb))
+                         .and_then(|port| {
+                             gdbserver::GdbServer::bind(port).map_err(|e| {
+                                 format!("Could not listen for gdb: {}", e)
+                             })
+                         });
+        match server {
+            Ok(server) => Some(server),
+            Err(e) => {
+                println!("{}", e);
+                std::process::exit(1);
+            }
+        }
+    };
+
+    let restart_pc = if args.flag_restart_pc.is_empty() {
+        None
+    } else {
+        match parse_number(&args.flag_restart_pc) {
+            Some(pc) if pc <= 0xFFFF => Some(pc as u16),
+            _ => {
+                println!("Invalid address {}", args.flag_restart_pc);
+                std::process::exit(1);
+            }
+        }
+    };
+    let vip_interpreter = if args.flag_vip.is_empty() {
+        None
+    } else {
+        Some(load_interpreter(&args).unwrap_or_else(|e| {
+            println!("{}", e);
+            std::process::exit(1);
+        }))
+    };
+    if args.flag_fast_forward <= 0.0 {
+        println!("Invalid fast-forward speed {}", args.flag_fast_forward);
+        std::process::exit(1);
+    }
+
+    let opengl = match &*args.flag_renderer {
+        "gl" => OpenGL::V3_2,
+        "software" => OpenGL::V2_1,
+        _ => {
+            println!("Unknown renderer {}", args.flag_renderer);
+            std::process::exit(1);
+        }
+    };
     let mut window: Sdl2Window = WindowSettings::new("Chip8.rs", [640, 320])
                                      .opengl(opengl)
                                      .exit_on_esc(true)
@@ -47,43 +300,53 @@ fn main() {
                                      .vsync(true)
                                      .build()
                                      .unwrap();
+    // gamepads are optional, carry on with the keyboard if SDL can't open them
+    if let Err(e) = window.init_joysticks() {
+        println!("Could not open gamepads: {}", e);
+    }
 
-    let mut app = app::App::init(GlGraphics::new(opengl),
+    let display: Box<display::Display> = if args.flag_renderer == "software" {
+        Box::new(display::SoftwareDisplay::with_window(GlGraphics::new(opengl)))
+    } else {
+        Box::new(display::GlDisplay::new(GlGraphics::new(opengl)))
+    };
+
+    let mut app = app::App::init(display,
                                  String::from(args.arg_filename.clone()),
-                                 if args.flag_speed % 60 == 0 {
-                                     args.flag_speed as usize
-                                 } else {
-                                     panic!("Clock speed {} is not divisible by 60, desync will \
-                                             occur.",
-                                            args.flag_speed);
-                                 },
-                                 if let Some((rgb, a)) =
-                                        read_color::rgb_maybe_a(&mut args.flag_foreground.chars()) {
-                                     [rgb[0],
-                                      rgb[1],
-                                      rgb[2],
-                                      if let Some(alpha) = a {
-                                          alpha
-                                      } else {
-                                          255
-                                      }]
-                                 } else {
-                                     [255, 255, 255, 255]
-                                 },
-                                 if let Some((rgb, a)) =
-                                        read_color::rgb_maybe_a(&mut args.flag_background.chars()) {
-                                     [rgb[0],
-                                      rgb[1],
-                                      rgb[2],
-                                      if let Some(alpha) = a {
-                                          alpha
-                                      } else {
-                                          255
-                                      }]
-                                 } else {
-                                     [0, 0, 0, 255]
-                                 },
-                                 args.flag_no_overdraw);
+                                 ipf,
+                                 palette,
+                                 args.flag_no_overdraw,
+                                 quirks,
+                                 seed,
+                                 args.flag_debug,
+                                 gdb,
+                                 tracer,
+                                 keymap,
+                                 args.flag_fast_forward);
+    let movie_option = match movie {
+        Some(movie) => app::MovieOption::Play(movie),
+        None if !args.flag_record.is_empty() => {
+            app::MovieOption::Record {
+                path: args.flag_record.clone(),
+                // a recording needs a seed to replay, pick one if none was given
+                seed: seed.unwrap_or_else(|| XorShift::from_time().state()),
+            }
+        }
+        None => app::MovieOption::Off,
+    };
+    if let Some(interpreter) = vip_interpreter {
+        app.run_on_vip(interpreter);
+    }
+    if let Err(e) = app.start_movie(movie_option) {
+        println!("Could not play {}: {}", args.flag_play, e);
+        std::process::exit(1);
+    }
+    if args.flag_watch {
+        app.watch_rom(restart_pc);
+    }
+    if args.flag_persistence > 0 {
+        app.set_persistence(args.flag_persistence);
+    }
 
         
     let mut events = window.events();
@@ -103,5 +366,120 @@ fn main() {
         if let Some(u) = e.release_args() {
             app.unkeypress(&u);
         }
+
+        if let Some(a) = e.controller_axis_args() {
+            app.axis(&a);
+        }
+    }
+    app.finish_movie();
+}
+
+// Parses a number in decimal or 0x hex
+fn parse_number(text: &str) -> Option<u64> {
+    if text.starts_with("0x") {
+        u64::from_str_radix(&text[2..], 16).ok()
+    } else {
+        text.parse().ok()
+    }
+}
+
+// Parses `first-last` or a single number, refusing a range that runs backwards
+fn parse_range(text: &str) -> Option<(u64, u64)> {
+    let mut parts = text.splitn(2, '-');
+    let first = match parts.next().and_then(parse_number) {
+        Some(first) => first,
+        None => return None,
+    };
+    match parts.next() {
+        Some(last) => parse_number(last).and_then(|last| if first <= last {
+            Some((first, last))
+        } else {
+            None
+        }),
+        None => Some((first, first)),
+    }
+}
+
+fn make_tracer(args: &Args) -> Result<Option<Tracer<BufWriter<File>>>, String> {
+    if args.flag_trace.is_empty() {
+        return Ok(None);
+    }
+    let file = try!(File::create(&args.flag_trace)
+                        .map_err(|e| format!("Could not create {}: {}", args.flag_trace, e)));
+    let mut tracer = Tracer::new(BufWriter::new(file));
+    if !args.flag_trace_pc.is_empty() {
+        for range in args.flag_trace_pc.split(',') {
+            match parse_range(range) {
+                Some((start, end)) if end <= 0xFFFF => {
+                    tracer.filter_addresses(start as u16, end as u16)
+                }
+                _ => return Err(format!("Invalid address range {}", range)),
+            }
+        }
+    }
+    if !args.flag_trace_frames.is_empty() {
+        match parse_range(&args.flag_trace_frames) {
+            Some((first, last)) => tracer.filter_frames(first, last),
+            None => return Err(format!("Invalid frame range {}", args.flag_trace_frames)),
+        }
+    }
+    Ok(Some(tracer))
+}
+
+// The interpreter image for --vip, refusing the options that need the built in core
+fn load_interpreter(args: &Args) -> Result<Vec<u8>, String> {
+    let unsupported = [(args.flag_debug, "--debug"),
+                       (!args.flag_gdb.is_empty(), "--gdb"),
+                       (!args.flag_record.is_empty(), "--record"),
+                       (!args.flag_play.is_empty(), "--play"),
+                       (!args.flag_trace.is_empty(), "--trace"),
+                       (!args.flag_restart_pc.is_empty(), "--restart-pc")];
+    if let Some(&(_, flag)) = unsupported.iter().find(|&&(set, _)| set) {
+        return Err(format!("{} can't be used with --vip", flag));
+    }
+    let mut interpreter = Vec::new();
+    try!(File::open(&args.flag_vip)
+             .and_then(|mut f| f.read_to_end(&mut interpreter))
+             .map_err(|e| format!("Could not read {}: {}", args.flag_vip, e)));
+    Ok(interpreter)
+}
+
+// $XDG_CONFIG_HOME, falling back to ~/.config as the XDG spec says when it's unset or empty
+fn config_dir() -> Option<PathBuf> {
+    match std::env::var_os("XDG_CONFIG_HOME") {
+        Some(ref dir) if !dir.is_empty() => Some(PathBuf::from(dir)),
+        _ => std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")),
+    }
+}
+
+// The keymap from --keymap or the user's config, with the section for this ROM applied
+fn load_keymap(args: &Args) -> Result<keymap::Keymap, String> {
+    let rom = Path::new(&args.arg_filename)
+                  .file_name()
+                  .map(|name| name.to_string_lossy().into_owned())
+                  .unwrap_or(String::new());
+    let path = if !args.flag_keymap.is_empty() {
+        PathBuf::from(&args.flag_keymap)
+    } else {
+        match config_dir().map(|dir| dir.join("chip8/keymap")) {
+            Some(ref path) if path.exists() => path.clone(),
+            _ => return Ok(keymap::Keymap::for_rom(&rom)),
+        }
+    };
+    keymap::Keymap::load(&path, &rom)
+}
+
+fn parse_color(hex: &str, default: [u8; 4]) -> [u8; 4] {
+    if let Some((rgb, a)) = read_color::rgb_maybe_a(&mut hex.chars()) {
+        [rgb[0],
+         rgb[1],
+         rgb[2],
+         if let Some(alpha) = a {
+             alpha
+         } else {
+             255
+         }]
+    } else {
+        default
     }
 }
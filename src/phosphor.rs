@@ -0,0 +1,69 @@
+// Imitates a CRT's slow phosphor so sprites erased and redrawn with XOR don't flicker.
+//
+// Every pixel has an intensity. A lit pixel is at full intensity and once it goes out it fades
+// to the background over a number of 60Hz frames, keeping the colour it was lit in while it
+// does. A sprite that's only off for a frame or two while it moves barely dims.
+
+/// Per pixel intensity that decays over `frames` frames.
+pub struct Phosphor {
+    frames: u32,
+    dimens: (usize, usize),
+    // colour index each pixel is glowing in
+    colors: Vec<u8>,
+    // 255 for lit pixels, falling to 0
+    intensity: Vec<u8>,
+    // set when a frame changed any pixel, cleared by whoever draws it
+    pub draw_flag: bool,
+}
+
+impl Phosphor {
+    pub fn new(frames: u32) -> Phosphor {
+        Phosphor {
+            frames: frames,
+            dimens: (0, 0),
+            colors: Vec::new(),
+            intensity: Vec::new(),
+            draw_flag: true,
+        }
+    }
+
+    /// Lights the pixels set in `gfx` and fades the rest by a frame. A change of resolution
+    /// starts over with nothing fading.
+    pub fn update(&mut self, gfx: &[u8], dimens: (usize, usize)) {
+        let size = dimens.0 * dimens.1;
+        if dimens != self.dimens {
+            self.dimens = dimens;
+            self.colors = vec![0; size];
+            self.intensity = vec![0; size];
+        }
+        // rounded up so a pixel is dark after exactly `frames` frames
+        let step = ((255 + self.frames - 1) / self.frames) as u8;
+        for (i, &pixel) in gfx[..size].iter().enumerate() {
+            let before = (self.colors[i], self.intensity[i]);
+            if pixel != 0 {
+                self.colors[i] = pixel;
+                self.intensity[i] = 255;
+            } else {
+                self.intensity[i] = self.intensity[i].saturating_sub(step);
+            }
+            if (self.colors[i], self.intensity[i]) != before {
+                self.draw_flag = true;
+            }
+        }
+    }
+
+    /// The screen size the buffers were last updated for.
+    pub fn dimens(&self) -> (usize, usize) {
+        self.dimens
+    }
+
+    /// Colour index of each pixel, in the same layout as the framebuffer.
+    pub fn colors(&self) -> &[u8] {
+        &self.colors
+    }
+
+    /// Intensity of each pixel from 0 to 255.
+    pub fn intensity(&self) -> &[u8] {
+        &self.intensity
+    }
+}
@@ -0,0 +1,119 @@
+use std::collections::VecDeque;
+
+/// Ring buffer of per-frame save states for playing emulation backwards.
+///
+/// Only the newest capture is kept whole. Every older one is stored as the XOR of itself and
+/// the capture after it, run-length encoded, so a frame that only touched a few bytes of memory
+/// and gfx costs a few bytes of history. Walking back applies the deltas newest first. Once the
+/// deltas exceed the byte budget the oldest ones are dropped.
+pub struct Rewind {
+    latest: Option<Vec<u8>>,
+    deltas: VecDeque<Vec<u8>>,
+    bytes: usize,
+    budget: usize,
+}
+
+impl Rewind {
+    pub fn new(budget: usize) -> Rewind {
+        Rewind {
+            latest: None,
+            deltas: VecDeque::new(),
+            bytes: 0,
+            budget: budget,
+        }
+    }
+
+    pub fn push(&mut self, state: Vec<u8>) {
+        if let Some(ref latest) = self.latest {
+            let delta = encode(latest, &state);
+            self.bytes += delta.len();
+            self.deltas.push_back(delta);
+        }
+        self.latest = Some(state);
+        while self.bytes > self.budget {
+            match self.deltas.pop_front() {
+                Some(delta) => self.bytes -= delta.len(),
+                None => break,
+            }
+        }
+    }
+
+    /// Steps back one capture and returns it, or None once the history is exhausted.
+    pub fn pop(&mut self) -> Option<Vec<u8>> {
+        let delta = match self.deltas.pop_back() {
+            Some(delta) => delta,
+            None => return None,
+        };
+        self.bytes -= delta.len();
+        let previous = decode(self.latest.as_ref().unwrap(), &delta);
+        self.latest = Some(previous.clone());
+        Some(previous)
+    }
+
+    pub fn clear(&mut self) {
+        self.latest = None;
+        self.deltas.clear();
+        self.bytes = 0;
+    }
+
+    #[allow(dead_code)]
+    pub fn len(&self) -> usize {
+        self.deltas.len()
+    }
+}
+
+fn push_varint(out: &mut Vec<u8>, mut v: usize) {
+    while v >= 0x80 {
+        out.push((v as u8) | 0x80);
+        v >>= 7;
+    }
+    out.push(v as u8);
+}
+
+fn read_varint(data: &[u8], pos: &mut usize) -> usize {
+    let mut v = 0;
+    let mut shift = 0;
+    loop {
+        let b = data[*pos];
+        *pos += 1;
+        v |= ((b & 0x7F) as usize) << shift;
+        if b & 0x80 == 0 {
+            return v;
+        }
+        shift += 7;
+    }
+}
+
+// Encodes older ^ newer as runs of (zero count, literal count, literal bytes)
+fn encode(older: &[u8], newer: &[u8]) -> Vec<u8> {
+    let xor: Vec<u8> = older.iter().zip(newer.iter()).map(|(a, b)| a ^ b).collect();
+    let mut out = Vec::new();
+    let mut i = 0;
+    while i < xor.len() {
+        let zeros = xor[i..].iter().take_while(|&&b| b == 0).count();
+        i += zeros;
+        let literals = xor[i..].iter().take_while(|&&b| b != 0).count();
+        push_varint(&mut out, zeros);
+        push_varint(&mut out, literals);
+        out.extend_from_slice(&xor[i..i + literals]);
+        i += literals;
+    }
+    out
+}
+
+// Recovers the older capture from the newer one and their delta
+fn decode(newer: &[u8], delta: &[u8]) -> Vec<u8> {
+    let mut out = newer.to_vec();
+    let mut pos = 0;
+    let mut i = 0;
+    while pos < delta.len() {
+        i += read_varint(delta, &mut pos);
+        let literals = read_varint(delta, &mut pos);
+        for b in &delta[pos..pos + literals] {
+            out[i] ^= *b;
+            i += 1;
+        }
+        pos += literals;
+    }
+    out
+}
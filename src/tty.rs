@@ -0,0 +1,354 @@
+// Plays a ROM in the terminal, for machines without a display server such as over SSH.
+//
+// Pixels are drawn two to a character cell with Unicode half blocks in ANSI 24 bit colour, or
+// eight to a cell with braille patterns in the foreground colour, which fits SCHIP's 128x64 in a
+// standard 80 column terminal. The terminal is put in raw mode so keys arrive as they're typed.
+// Terminals don't report key releases, so a hex key counts as held for a few frames after its
+// key was last seen, and autorepeat keeps it held for as long as the key is.
+
+use std::fmt::Write as FmtWrite;
+use std::io::{self, Read, Write};
+use std::mem;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use libc;
+use piston::input::Key;
+
+use app::read_rom;
+use chip8_core::{Chip8, Quirks, StepOutcome};
+use keymap::Keymap;
+
+// Frames a hex key stays down after its key was last typed, long enough to bridge the pause
+// before most terminals start repeating a held key
+const KEY_HOLD_FRAMES: u32 = 30;
+
+pub struct Options {
+    // instructions per 60Hz frame
+    pub ipf: usize,
+    pub quirks: Quirks,
+    pub seed: Option<u64>,
+    pub keymap: Keymap,
+    // background, plane 1, plane 2 and both planes, alpha is ignored
+    pub palette: [[u8; 4]; 4],
+    // draw with braille instead of half blocks
+    pub braille: bool,
+}
+
+// Raw mode for as long as it lives, along with the alternate screen and a hidden cursor
+struct RawTerminal {
+    original: libc::termios,
+}
+
+impl RawTerminal {
+    fn enter() -> io::Result<RawTerminal> {
+        let original = unsafe {
+            let mut termios: libc::termios = mem::zeroed();
+            if libc::tcgetattr(libc::STDIN_FILENO, &mut termios) != 0 {
+                return Err(io::Error::last_os_error());
+            }
+            let original = termios;
+            libc::cfmakeraw(&mut termios);
+            // reads return straight away with whatever has been typed so far
+            termios.c_cc[libc::VMIN] = 0;
+            termios.c_cc[libc::VTIME] = 0;
+            if libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &termios) != 0 {
+                return Err(io::Error::last_os_error());
+            }
+            original
+        };
+        print!("\x1b[?1049h\x1b[?25l\x1b[2J");
+        try!(io::stdout().flush());
+        Ok(RawTerminal { original: original })
+    }
+}
+
+impl Drop for RawTerminal {
+    fn drop(&mut self) {
+        print!("\x1b[0m\x1b[?25h\x1b[?1049l");
+        let _ = io::stdout().flush();
+        unsafe {
+            libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &self.original);
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Input {
+    Quit,
+    Key(Key),
+}
+
+// Escape or Ctrl-C quit, arrow keys arrive as escape sequences and everything else is a
+// character, with shift ignored. Arrows are `ESC [ A` normally and `ESC O A` in application
+// cursor mode.
+//
+// A sequence split across reads is kept in `pending` until the rest arrives. An escape that
+// could still start one only counts as the Escape key once a read comes back empty, which with
+// a read every frame means nothing followed it for a frame.
+fn parse_input(pending: &mut Vec<u8>, bytes: &[u8]) -> Vec<Input> {
+    let timed_out = bytes.is_empty();
+    pending.extend_from_slice(bytes);
+    let mut inputs = Vec::new();
+    let mut i = 0;
+    while i < pending.len() {
+        match pending[i] {
+            0x1b if starts_sequence(pending.get(i + 1), timed_out) => {
+                let end = if pending.get(i + 1) == Some(&b'O') {
+                    // SS3 is always followed by just the final byte
+                    pending.get(i + 2).map(|_| i + 2)
+                } else {
+                    // parameters such as the modifiers in `ESC [ 1 ; 5 A` run up to a final byte
+                    pending[i + 1..]
+                        .iter()
+                        .skip(1)
+                        .position(|&b| b >= 0x40 && b <= 0x7e)
+                        .map(|n| i + 2 + n)
+                };
+                match end {
+                    Some(end) => {
+                        match pending[end] {
+                            b'A' => inputs.push(Input::Key(Key::Up)),
+                            b'B' => inputs.push(Input::Key(Key::Down)),
+                            b'C' => inputs.push(Input::Key(Key::Right)),
+                            b'D' => inputs.push(Input::Key(Key::Left)),
+                            _ => {}
+                        }
+                        i = end + 1;
+                        continue;
+                    }
+                    // wait for the rest of the sequence
+                    None if !timed_out => break,
+                    // it never finished, so it was the Escape key after all
+                    None => inputs.push(Input::Quit),
+                }
+            }
+            0x1b | 0x03 => inputs.push(Input::Quit),
+            c @ 0x20...0x7e => {
+                let lower = (c as char).to_lowercase().next().unwrap();
+                inputs.push(Input::Key(Key::from(lower as u32)));
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+    pending.drain(..i);
+    inputs
+}
+
+// Whether an escape followed by `next` is the start of a CSI or SS3 sequence, or might be once
+// more is read
+fn starts_sequence(next: Option<&u8>, timed_out: bool) -> bool {
+    match next {
+        Some(&b'[') | Some(&b'O') => true,
+        Some(_) => false,
+        None => !timed_out,
+    }
+}
+
+fn set_color(out: &mut String, layer: u8, color: [u8; 4]) {
+    let _ = write!(out, "\x1b[{};2;{};{};{}m", layer, color[0], color[1], color[2]);
+}
+
+// Each cell is the upper half block in the top pixel's colour over the bottom pixel's colour
+fn render_half_blocks(out: &mut String,
+                      gfx: &[u8],
+                      dimens: (usize, usize),
+                      palette: &[[u8; 4]; 4]) {
+    let (width, height) = dimens;
+    for y in (0..height).filter(|y| y % 2 == 0) {
+        // colours are reset at the end of each line, so nothing bleeds into the margin
+        let mut current = None;
+        for x in 0..width {
+            let top = palette[(gfx[y * width + x] & 3) as usize];
+            let bottom = palette[(gfx[(y + 1) * width + x] & 3) as usize];
+            if current != Some((top, bottom)) {
+                set_color(out, 38, top);
+                set_color(out, 48, bottom);
+                current = Some((top, bottom));
+            }
+            out.push('▀');
+        }
+        out.push_str("\x1b[0m\r\n");
+    }
+}
+
+// Each cell is a braille pattern of 2x4 pixels, any set pixel showing in the foreground colour
+fn render_braille(out: &mut String,
+                  gfx: &[u8],
+                  dimens: (usize, usize),
+                  palette: &[[u8; 4]; 4]) {
+    // dot bits in the order the pixels are visited, left column then right
+    const DOTS: [[u32; 4]; 2] = [[0x01, 0x02, 0x04, 0x40], [0x08, 0x10, 0x20, 0x80]];
+    let (width, height) = dimens;
+    for y in (0..height).filter(|y| y % 4 == 0) {
+        set_color(out, 38, palette[1]);
+        set_color(out, 48, palette[0]);
+        for x in (0..width).filter(|x| x % 2 == 0) {
+            let mut pattern = 0;
+            for dx in 0..2 {
+                for dy in 0..4 {
+                    if gfx[(y + dy) * width + x + dx] != 0 {
+                        pattern |= DOTS[dx][dy];
+                    }
+                }
+            }
+            out.push(::std::char::from_u32(0x2800 + pattern).unwrap());
+        }
+        out.push_str("\x1b[0m\r\n");
+    }
+}
+
+// Registers for the line under the screen, where a fault also shows
+fn status(c8: &Chip8, halted: &Option<String>) -> String {
+    let mut line = format!("PC {:04X}  I {:04X}  V", c8.pc(), c8.i());
+    for v in c8.v().iter() {
+        let _ = write!(line, " {:02X}", v);
+    }
+    match *halted {
+        Some(ref message) => line.push_str(&format!("  {}", message)),
+        None => line.push_str("  Esc quits"),
+    }
+    line
+}
+
+// Runs one 60Hz frame, returning why execution stopped if it did
+fn run_frame(c8: &mut Chip8, ipf: usize) -> Option<String> {
+    for _ in 0..ipf {
+        match c8.step() {
+            Ok(StepOutcome::Executed) |
+            Ok(StepOutcome::WaitingForKey) => {}
+            Ok(StepOutcome::WaitingForVblank) => break,
+            Ok(StepOutcome::Exited) => return Some(String::from("Exited")),
+            Err(e) => return Some(format!("Emulation halted: {}", e)),
+        }
+    }
+    c8.tick();
+    None
+}
+
+pub fn run(program_file: String, options: Options) -> io::Result<()> {
+    // read before raw mode, so a bad path fails with the terminal as it was
+    let rom = try!(read_rom(&program_file).map_err(|e| {
+        io::Error::new(e.kind(), format!("could not read {}: {}", program_file, e))
+    }));
+    let mut c8 = Chip8::init();
+    c8.quirks = options.quirks;
+    if let Some(seed) = options.seed {
+        c8.seed(seed);
+    }
+    c8.load_rom(&rom);
+
+    let _terminal = try!(RawTerminal::enter());
+    let frame_time = Duration::new(0, 1_000_000_000 / 60);
+    let mut next_frame = Instant::now();
+    // frames left for each hex key before it's released
+    let mut held = [0u32; 16];
+    let mut halted = None;
+    let mut dimens = c8.screen_dimens();
+    let mut beeping = false;
+    let mut input = [0; 64];
+    // the start of an escape sequence whose end hasn't been read yet
+    let mut pending = Vec::new();
+
+    loop {
+        let count = try!(io::stdin().read(&mut input));
+        for event in parse_input(&mut pending, &input[..count]) {
+            match event {
+                Input::Quit => return Ok(()),
+                Input::Key(key) => {
+                    if let Some(hex) = options.keymap.hex_key(key) {
+                        if held[hex as usize] == 0 {
+                            c8.update_keys(hex, true);
+                        }
+                        held[hex as usize] = KEY_HOLD_FRAMES;
+                    }
+                }
+            }
+        }
+        for hex in 0..16 {
+            if held[hex] > 0 {
+                held[hex] -= 1;
+                if held[hex] == 0 {
+                    c8.update_keys(hex as u8, false);
+                }
+            }
+        }
+
+        if halted.is_none() {
+            halted = run_frame(&mut c8, options.ipf);
+        }
+
+        let mut out = String::new();
+        // the bell is as close as a terminal gets to the buzzer
+        if c8.sound_timer > 0 && !beeping {
+            out.push('\x07');
+        }
+        beeping = c8.sound_timer > 0;
+        if c8.screen_dimens() != dimens {
+            dimens = c8.screen_dimens();
+            out.push_str("\x1b[2J");
+            c8.draw_flag = true;
+        }
+        if c8.draw_flag {
+            out.push_str("\x1b[H");
+            if options.braille {
+                render_braille(&mut out, &c8.gfx, dimens, &options.palette);
+            } else {
+                render_half_blocks(&mut out, &c8.gfx, dimens, &options.palette);
+            }
+            c8.draw_flag = false;
+        } else {
+            // skip to the status line
+            let rows = dimens.1 / if options.braille { 4 } else { 2 };
+            let _ = write!(out, "\x1b[{};1H", rows + 1);
+        }
+        out.push_str("\x1b[K");
+        out.push_str(&status(&c8, &halted));
+        try!(io::stdout().write_all(out.as_bytes()));
+        try!(io::stdout().flush());
+
+        next_frame += frame_time;
+        let now = Instant::now();
+        if next_frame > now {
+            thread::sleep(next_frame - now);
+        } else {
+            // fell behind, don't try to catch up
+            next_frame = now;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_input, Input};
+    use piston::input::Key;
+
+    #[test]
+    fn arrows() {
+        let mut pending = Vec::new();
+        assert_eq!(parse_input(&mut pending, b"\x1b[A\x1bOB\x1b[1;5C"),
+                   vec![Input::Key(Key::Up), Input::Key(Key::Down), Input::Key(Key::Right)]);
+        // sequences split across reads wait for the rest
+        assert_eq!(parse_input(&mut pending, b"\x1b"), vec![]);
+        assert_eq!(parse_input(&mut pending, b"O"), vec![]);
+        assert_eq!(parse_input(&mut pending, b"Dq"),
+                   vec![Input::Key(Key::Left), Input::Key(Key::Q)]);
+        assert_eq!(parse_input(&mut pending, b"\x1b[1;"), vec![]);
+        assert_eq!(parse_input(&mut pending, b"5A"), vec![Input::Key(Key::Up)]);
+        // other sequences are skipped whole
+        assert_eq!(parse_input(&mut pending, b"\x1b[15~w"), vec![Input::Key(Key::W)]);
+        assert!(pending.is_empty());
+    }
+
+    #[test]
+    fn escape() {
+        let mut pending = Vec::new();
+        // a lone escape quits once nothing follows it
+        assert_eq!(parse_input(&mut pending, b"\x1b"), vec![]);
+        assert_eq!(parse_input(&mut pending, b""), vec![Input::Quit]);
+        assert_eq!(parse_input(&mut pending, b"\x1bq\x03"),
+                   vec![Input::Quit, Input::Key(Key::Q), Input::Quit]);
+        assert!(pending.is_empty());
+    }
+}
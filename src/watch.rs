@@ -0,0 +1,48 @@
+use std::fs;
+use std::path::PathBuf;
+use std::time::SystemTime;
+
+// How often to look at the file, in seconds
+const POLL_INTERVAL: f64 = 0.5;
+
+/// Notices when a file is rewritten by polling its modification time.
+///
+/// Polling keeps this free of platform specific notification APIs, and twice a second is plenty
+/// for a rebuild to show up while the window is in view.
+pub struct FileWatch {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+    elapsed: f64,
+}
+
+fn modified(path: &PathBuf) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+impl FileWatch {
+    pub fn new<P: Into<PathBuf>>(path: P) -> FileWatch {
+        let path = path.into();
+        FileWatch {
+            modified: modified(&path),
+            path: path,
+            elapsed: 0.0,
+        }
+    }
+
+    /// Counts `dt` seconds, returning true when the file has changed since it was last seen.
+    /// A file that can't be read, say while it's being replaced, doesn't count as a change.
+    pub fn poll(&mut self, dt: f64) -> bool {
+        self.elapsed += dt;
+        if self.elapsed < POLL_INTERVAL {
+            return false;
+        }
+        self.elapsed = 0.0;
+        match modified(&self.path) {
+            Some(time) if Some(time) != self.modified => {
+                self.modified = Some(time);
+                true
+            }
+            _ => false,
+        }
+    }
+}